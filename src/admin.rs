@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use twitch_api2::helix::search::search_channels;
+use twitch_api2::helix::HelixClient;
+use twitch_api2::types::UserId;
+
+use crate::auth::require_bearer;
+use crate::config::Setting;
+use crate::options::{FeedQuery, VideoFilter};
+#[cfg(feature = "templates")]
+use crate::templates;
+use crate::tracked::{ChannelStatus, TrackedChannel};
+#[cfg(feature = "eventsub")]
+use crate::websub;
+use crate::{cache, channel_feed, get_token, handle_helix_error, login, notify, provider};
+use crate::{AppState, TwitchRssError};
+
+pub fn router(token: String) -> Router<AppState> {
+    let api = Router::new()
+        .route("/channels", get(list_channels).post(post_channel))
+        .route(
+            "/channels/{name}",
+            get(get_channel).put(put_channel).delete(delete_channel),
+        )
+        .route("/status", get(status))
+        .route("/cache/{name}", delete(purge_cache))
+        .route("/search", get(search))
+        .route("/config", get(config));
+
+    // the page itself carries no data, so it is left outside the token check
+    Router::new()
+        .route("/", get(ui))
+        .merge(require_bearer(api, token))
+}
+
+/// A browser can't attach the bearer token to a plain page load, so everything the page shows
+/// is fetched from the endpoints above using a token entered on the page.
+#[cfg_attr(not(feature = "templates"), allow(unused_variables))]
+async fn ui(State(state): State<AppState>) -> Html<String> {
+    #[cfg(feature = "templates")]
+    if let Some(page) = state
+        .templates
+        .and_then(|t| t.render(templates::ADMIN_PAGE, &tera::Context::new()))
+    {
+        return Html(page);
+    }
+
+    Html(include_str!("admin.html").to_string())
+}
+
+async fn list_channels(State(state): State<AppState>) -> Json<BTreeMap<String, TrackedChannel>> {
+    Json(state.tracked.list().await)
+}
+
+async fn get_channel(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<TrackedChannel>, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    state
+        .tracked
+        .get(&name)
+        .await
+        .map(Json)
+        .ok_or(TwitchRssError::NotTracked(name))
+}
+
+async fn put_channel(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(channel): Json<TrackedChannel>,
+) -> Result<Json<TrackedChannel>, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    track(&state, name, channel).await.map(Json)
+}
+
+/// Body of `POST /admin/channels`, the login next to the settings of `PUT`.
+#[derive(Deserialize)]
+struct NewChannel {
+    name: String,
+    #[serde(flatten)]
+    settings: TrackedChannel,
+}
+
+/// Add a channel given in the body, for clients that would rather not build the URL.
+async fn post_channel(
+    State(state): State<AppState>,
+    Json(channel): Json<NewChannel>,
+) -> Result<(StatusCode, Json<TrackedChannel>), TwitchRssError> {
+    let name = login::parse_login(&channel.name)?;
+
+    let settings = track(&state, name, channel.settings).await?;
+    Ok((StatusCode::CREATED, Json(settings)))
+}
+
+async fn track(
+    state: &AppState,
+    name: String,
+    channel: TrackedChannel,
+) -> Result<TrackedChannel, TwitchRssError> {
+    state.tracked.set(name.clone(), channel.clone()).await?;
+
+    // the channel is tracked either way, subscribing is retried on the next start
+    #[cfg(feature = "eventsub")]
+    if let Err(e) = crate::eventsub::subscribe(state, &name).await {
+        println!("{}", e);
+    }
+
+    // in the background, a channel Twitch doesn't know is still tracked as asked
+    tokio::spawn(warm(state.clone(), name));
+
+    Ok(channel)
+}
+
+/// Fetch the VOD feed of `login` so its first reader is served from the cache.
+async fn warm(state: AppState, login: String) {
+    if let Err(e) = channel_feed(&state, &login, FeedQuery::default()).await {
+        println!("warming {}: {}", login, e);
+    }
+}
+
+/// Warm the feed of every tracked channel at startup, then keep checking the videos of channels
+/// that announce or archive them, which has to happen without readers asking. Checks follow
+/// `VIDEOS_CACHE_TTL`, when a cached list can first have changed.
+pub async fn watch_tracked(state: AppState) {
+    for login in state.tracked.list().await.into_keys() {
+        warm(state.clone(), login).await;
+    }
+
+    // the first check right away only notes the latest video of each channel
+    let mut interval = tokio::time::interval(state.config.videos_ttl);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        for (login, channel) in state.tracked.list().await {
+            if !channel.is_watched() {
+                continue;
+            }
+            match provider::twitch_videos(&state, &login, &VideoFilter::default()).await {
+                Ok(videos) => notify::announce(&state, &login, &channel.notify, &videos).await,
+                Err(e) => println!("checking videos of {}: {}", login, e),
+            }
+        }
+    }
+}
+
+async fn delete_channel(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    if state.tracked.remove(&name).await? {
+        #[cfg(feature = "eventsub")]
+        if let Some(eventsub) = &state.eventsub {
+            eventsub.forget(&name).await;
+        }
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(TwitchRssError::NotTracked(name))
+    }
+}
+
+async fn status(State(state): State<AppState>) -> Json<HashMap<String, ChannelStatus>> {
+    Json(state.tracked.status().await)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+}
+
+#[derive(Serialize)]
+struct SearchResult {
+    login: String,
+    display_name: String,
+    title: String,
+    thumbnail_url: String,
+    is_live: bool,
+}
+
+async fn search(
+    Query(query): Query<SearchQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<SearchResult>>, TwitchRssError> {
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client: HelixClient<_> = HelixClient::with_client(state.client.clone());
+
+    let request = search_channels::SearchChannelsRequest::builder()
+        .query(query.q)
+        .first(10)
+        .build();
+
+    let channels = helix_client
+        .req_get(request, &token)
+        .await
+        .map_err(handle_helix_error)?
+        .data;
+
+    Ok(Json(
+        channels
+            .into_iter()
+            .map(|c| SearchResult {
+                login: c.broadcaster_login.to_string(),
+                display_name: c.display_name.to_string(),
+                title: c.title,
+                thumbnail_url: c.thumbnail_url,
+                is_live: c.is_live,
+            })
+            .collect(),
+    ))
+}
+
+/// The effective configuration, to check what a deployment actually picked up.
+async fn config(State(state): State<AppState>) -> Json<Vec<Setting>> {
+    Json(state.config.effective())
+}
+
+/// Drop everything cached for a channel so the next feed request goes to Twitch.
+async fn purge_cache(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    let user_id_key = cache::user_id_key(&name);
+    let user_id = state.cache.get::<UserId>(&user_id_key).await;
+    cache::invalidate_responses(&state.cache, &name, user_id.as_ref()).await;
+    if let Some(user_id) = user_id {
+        cache::invalidate_videos(&state.cache, &user_id).await;
+        cache::invalidate_clips(&state.cache, &user_id).await;
+        for key in [
+            cache::user_by_id_key(&user_id),
+            cache::channel_tags_key(&user_id),
+            cache::schedule_key(&user_id),
+        ] {
+            state.cache.invalidate(&key).await;
+        }
+    }
+    for key in [
+        user_id_key,
+        cache::user_key(&name),
+        cache::channel_key(&name),
+        cache::suggestions_key(&name),
+    ] {
+        state.cache.invalidate(&key).await;
+    }
+
+    // subscribers through the hub see the purged feed as soon as readers polling it
+    #[cfg(feature = "eventsub")]
+    if state.tracked.get(&name).await.is_some() {
+        websub::publish(&state, &name).await;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,99 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde::Serialize;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::types::{BroadcasterType, VideoType};
+
+use crate::feed;
+use crate::latest::VideoSummary;
+use crate::options::{FeedOptions, FeedQuery};
+use crate::{live, login, provider, AppState, TwitchRssError};
+
+/// `/api/channel/{name}`, who a channel is.
+#[derive(Serialize)]
+pub struct ChannelInfo {
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+    pub description: String,
+    pub profile_image: Option<String>,
+    /// `partner` or `affiliate`, `null` for everyone else
+    pub broadcaster_type: Option<BroadcasterType>,
+    pub live: bool,
+}
+
+/// `/api/channel/{name}`, the user behind a channel and whether it is live, as JSON.
+pub async fn channel(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<ChannelInfo>, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    let (user, streams) = tokio::try_join!(
+        provider::twitch_user(&state, &name),
+        live::live_streams(&state, std::slice::from_ref(&name))
+    )?;
+
+    Ok(Json(ChannelInfo {
+        id: user.id.to_string(),
+        login: user.login.to_string(),
+        display_name: user.display_name.to_string(),
+        description: user.description.unwrap_or_default(),
+        profile_image: user.profile_image_url.filter(|url| !url.is_empty()),
+        broadcaster_type: user
+            .broadcaster_type
+            .filter(|t| *t != BroadcasterType::None),
+        live: !streams.is_empty(),
+    }))
+}
+
+/// A video in `/api/channel/{name}/videos`, the fields of `latest.json` and a few more.
+#[derive(Serialize)]
+pub struct ChannelVideo {
+    #[serde(flatten)]
+    pub summary: VideoSummary,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub video_type: VideoType,
+    pub views: i64,
+    pub language: String,
+    pub sub_only: bool,
+    /// The broadcast the video was recorded of, only for archives
+    pub stream_id: Option<String>,
+}
+
+impl ChannelVideo {
+    fn new(video: &Video, size: feed::ThumbnailSize) -> Self {
+        Self {
+            summary: VideoSummary::new(video, size),
+            description: video.description.clone(),
+            video_type: video.type_.clone(),
+            views: video.view_count,
+            language: video.language.clone(),
+            sub_only: feed::is_sub_only(video),
+            stream_id: video.stream_id.as_ref().map(|id| id.to_string()),
+        }
+    }
+}
+
+/// `/api/channel/{name}/videos`, the videos of a channel as JSON, for dashboards and scripts that
+/// don't want to parse a feed.
+///
+/// The query parameters that pick videos from Twitch (`type`, `limit`, `pages`, `all`, `period`,
+/// `sort`, `lang`, `public`) apply, and the list is cached with the channel's feed.
+pub async fn channel_videos(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ChannelVideo>>, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let overrides = state.overrides.get(&name);
+    let videos = provider::twitch_videos(&state, &name, &options.videos).await?;
+
+    let size = overrides.thumbnail_size.unwrap_or_default();
+    Ok(Json(
+        videos.iter().map(|v| ChannelVideo::new(v, size)).collect(),
+    ))
+}
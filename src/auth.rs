@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::Router;
+
+use crate::{AppState, TwitchRssError};
+
+/// Wrap every route of `router` so it requires `Authorization: Bearer <token>`.
+///
+/// This is meant for operator facing routes (admin, debug, cache, metrics) and is deliberately
+/// separate from anything used to access feeds.
+pub fn require_bearer(router: Router<AppState>, token: String) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state(
+        Arc::<str>::from(token),
+        check_bearer,
+    ))
+}
+
+async fn check_bearer(
+    State(expected): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, TwitchRssError> {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(TwitchRssError::AdminUnauthorized)?;
+
+    if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        Ok(next.run(request).await)
+    } else {
+        Err(TwitchRssError::AdminUnauthorized)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use twitch_api2::types::{CategoryId, UserId, VideoId};
+
+/// Storage for cached Twitch responses.
+///
+/// Values are opaque bytes so a store can live outside the process (Redis, memcached, sled, ...).
+/// Stores are free to evict entries early; an entry must not be returned after its TTL elapsed.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration);
+
+    async fn invalidate(&self, key: &str);
+}
+
+/// How often [`MemoryStore`] looks through all entries for expired ones
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// In-process store, the default.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    values: HashMap<String, (Instant, Vec<u8>)>,
+    /// When expired entries are dropped next, `None` before the first insert
+    next_sweep: Option<Instant>,
+}
+
+#[async_trait]
+impl CacheStore for MemoryStore {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().await;
+        match entries.values.get(key) {
+            Some((expires, value)) if *expires > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                entries.values.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().await;
+        // keys that are never requested again would pile up, expired ones are dropped now and
+        // then rather than on every insert
+        if entries.next_sweep.is_none_or(|at| at <= now) {
+            entries.values.retain(|_, (expires, _)| *expires > now);
+            entries.next_sweep = Some(now + SWEEP_INTERVAL);
+        }
+        entries.values.insert(key.to_string(), (now + ttl, value));
+    }
+
+    async fn invalidate(&self, key: &str) {
+        self.entries.lock().await.values.remove(key);
+    }
+}
+
+/// Typed access to a [`CacheStore`], storing values as JSON.
+#[derive(Clone)]
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+}
+
+impl Cache {
+    pub fn new(store: Arc<dyn CacheStore>) -> Self {
+        Self { store }
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.store
+            .get(key)
+            .await
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) {
+        if let Ok(bytes) = serde_json::to_vec(value) {
+            self.store.set(key, bytes, ttl).await;
+        }
+    }
+
+    #[cfg_attr(not(feature = "admin"), allow(dead_code))]
+    pub async fn invalidate(&self, key: &str) {
+        self.store.invalidate(key).await
+    }
+
+    /// Return the cached value for `key`, or run `f` and cache its result if it succeeds.
+    ///
+    /// Entries that fail to deserialize (e.g. written by an older version) are treated as
+    /// missing.
+    pub async fn get_or_try_insert<T, E, F, Fut>(
+        &self,
+        key: &str,
+        ttl: Duration,
+        f: F,
+    ) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.get(key).await {
+            return Ok(value);
+        }
+
+        let value = f().await?;
+        if let Ok(bytes) = serde_json::to_vec(&value) {
+            self.store.set(key, bytes, ttl).await;
+        }
+        Ok(value)
+    }
+}
+
+pub fn user_id_key(login: &str) -> String {
+    format!("user_id:{}", login)
+}
+
+pub fn user_key(login: &str) -> String {
+    format!("user:{}", login)
+}
+
+pub fn suggestions_key(login: &str) -> String {
+    format!("suggestions:{}", login)
+}
+
+/// Responses differ by `Accept` where the format is negotiated. `generation` is that of the
+/// channel in the URL, see [`responses_generation`].
+pub fn response_key(accept: &str, generation: u64, path_and_query: &str) -> String {
+    format!("response:{}:{}:{}", accept, generation, path_and_query)
+}
+
+pub fn user_by_id_key(user_id: &UserId) -> String {
+    format!("user_by_id:{}", user_id)
+}
+
+#[cfg(feature = "media")]
+pub fn media_key(link: &str) -> String {
+    format!("media:{}", link)
+}
+
+pub fn stream_category_key(stream_id: &str) -> String {
+    format!("stream_category:{}", stream_id)
+}
+
+pub fn channel_key(login: &str) -> String {
+    format!("channel:{}", login)
+}
+
+pub fn channel_tags_key(user_id: &UserId) -> String {
+    format!("channel_tags:{}", user_id)
+}
+
+pub fn videos_key(user_id: &UserId, generation: u64, filter: &str) -> String {
+    format!("videos:{}:{}:{}", user_id, generation, filter)
+}
+
+fn generation_key(list: &str, id: &str) -> String {
+    format!("{}_generation:{}", list, id)
+}
+
+/// Outlives every cached list, so an old generation can't come back.
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+const GENERATION_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+async fn generation(cache: &Cache, list: &str, id: &str) -> u64 {
+    cache.get(&generation_key(list, id)).await.unwrap_or(0)
+}
+
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+async fn bump_generation(cache: &Cache, list: &str, id: &str) {
+    let next = generation(cache, list, id).await + 1;
+    cache
+        .set(&generation_key(list, id), &next, GENERATION_TTL)
+        .await;
+}
+
+/// Video lists are filtered in too many ways to invalidate one by one, instead their keys contain
+/// a generation that is bumped to drop all of them.
+pub async fn videos_generation(cache: &Cache, user_id: &UserId) -> u64 {
+    generation(cache, "videos", user_id.as_str()).await
+}
+
+/// Drop every cached list of videos of `user_id`.
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub async fn invalidate_videos(cache: &Cache, user_id: &UserId) {
+    bump_generation(cache, "videos", user_id.as_str()).await;
+}
+
+/// Like [`videos_generation`], clips are cached for any window that was asked for.
+pub async fn clips_generation(cache: &Cache, user_id: &UserId) -> u64 {
+    generation(cache, "clips", user_id.as_str()).await
+}
+
+/// Drop every cached list of clips of `user_id`.
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub async fn invalidate_clips(cache: &Cache, user_id: &UserId) {
+    bump_generation(cache, "clips", user_id.as_str()).await;
+}
+
+/// How the URL of a response names its channel.
+pub enum UrlChannel {
+    Login(String),
+    UserId(String),
+}
+
+impl UrlChannel {
+    fn id(&self) -> String {
+        match self {
+            Self::Login(login) => format!("login:{}", login),
+            Self::UserId(user_id) => format!("id:{}", user_id),
+        }
+    }
+}
+
+/// Rendered responses are kept by URL, so like video lists they are dropped by bumping a
+/// generation, one for each way a URL can name the channel.
+pub async fn responses_generation(cache: &Cache, channel: &UrlChannel) -> u64 {
+    generation(cache, "responses", &channel.id()).await
+}
+
+/// Drop every rendered response of the channel `login`, with `user_id` if it is known.
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub async fn invalidate_responses(cache: &Cache, login: &str, user_id: Option<&UserId>) {
+    let login = UrlChannel::Login(login.to_string());
+    bump_generation(cache, "responses", &login.id()).await;
+    if let Some(user_id) = user_id {
+        let user_id = UrlChannel::UserId(user_id.to_string());
+        bump_generation(cache, "responses", &user_id.id()).await;
+    }
+}
+
+pub fn video_key(video_id: &VideoId) -> String {
+    format!("video:{}", video_id)
+}
+
+/// Game names are matched case-insensitively, so differently cased requests share an entry.
+pub fn game_key(name: &str) -> String {
+    format!("game:{}", name.to_lowercase())
+}
+
+pub fn game_videos_key(game_id: &CategoryId) -> String {
+    format!("game_videos:{}", game_id)
+}
+
+pub fn game_streams_key(game_id: &CategoryId) -> String {
+    format!("game_streams:{}", game_id)
+}
+
+pub fn game_clips_key(game_id: &CategoryId, window: &str) -> String {
+    format!("game_clips:{}:{}", game_id, window)
+}
+
+pub fn schedule_key(user_id: &UserId) -> String {
+    format!("schedule:{}", user_id)
+}
+
+pub fn team_key(name: &str) -> String {
+    format!("team:{}", name)
+}
+
+pub fn clips_key(user_id: &UserId, generation: u64, window: &str) -> String {
+    format!("clips:{}:{}:{}", user_id, generation, window)
+}
+
+/// Key for the live streams of a set of channels, independent of the order they were given in.
+pub fn streams_key(logins: &[String]) -> String {
+    let mut logins = logins.to_vec();
+    logins.sort();
+    format!("streams:{}", logins.join(","))
+}
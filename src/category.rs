@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use twitch_api2::helix::streams::Stream;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::types::VideoType;
+
+use crate::feed::FeedItem;
+use crate::i18n::Locale;
+use crate::{cache, live, AppState, TwitchRssError};
+
+/// As long as the longest kept archives, so a category is known for as long as its video exists.
+const CATEGORY_TTL: Duration = Duration::from_secs(60 * 24 * 60 * 60);
+
+/// Archives created this recently may still be recording, and belong to the running stream.
+const RECORDING: time::Duration = time::Duration::hours(48);
+
+/// Helix doesn't say which category a video was streamed under, but a live stream does. It is
+/// remembered by stream id for the archive of that stream.
+pub async fn remember(state: &AppState, stream: &Stream) {
+    if !stream.game_name.is_empty() {
+        state
+            .cache
+            .set(
+                &cache::stream_category_key(stream.id.as_str()),
+                &stream.game_name,
+                CATEGORY_TTL,
+            )
+            .await;
+    }
+}
+
+/// Add the category of their video to `items` where it is known, either remembered from the
+/// stream or, for tracked channels, from the channel's recorded changes.
+///
+/// `items` and `videos` have to be in the same order, as returned by
+/// [`provider::twitch_items`](crate::provider::twitch_items).
+pub async fn annotate(
+    state: &AppState,
+    channel: &str,
+    items: Vec<FeedItem>,
+    videos: &[Video],
+    locale: Locale,
+) -> Result<Vec<FeedItem>, TwitchRssError> {
+    let now = OffsetDateTime::now_utc();
+    let recording = videos.iter().any(|v| {
+        v.type_ == VideoType::Archive
+            && v.stream_id.is_some()
+            && v.created_at.to_utc() > now - RECORDING
+    });
+    if recording && !zero_poll(state, channel).await? {
+        // looking the stream up remembers its category
+        live::live_streams(state, &[channel.to_string()]).await?;
+    }
+
+    #[cfg(feature = "eventsub")]
+    let changes = match &state.eventsub {
+        Some(eventsub) => eventsub.changes(channel).await,
+        None => Vec::new(),
+    };
+
+    let mut annotated = Vec::with_capacity(items.len());
+    for (item, video) in items.into_iter().zip(videos) {
+        let category = match &video.stream_id {
+            Some(id) => {
+                state
+                    .cache
+                    .get::<String>(&cache::stream_category_key(id.as_str()))
+                    .await
+            }
+            None => None,
+        };
+
+        // the category the channel was set to when the video started
+        #[cfg(feature = "eventsub")]
+        let category = category.or_else(|| {
+            let started = video.created_at.to_utc();
+            changes
+                .iter()
+                .find(|c| c.at <= started)
+                .map(|c| c.category_name.clone())
+        });
+
+        match category {
+            Some(category) => annotated.push(FeedItem {
+                description: format!(
+                    "{}<br />{}: {}",
+                    item.description,
+                    locale.category(),
+                    category
+                ),
+                category: Some(category),
+                ..item
+            }),
+            None => annotated.push(item),
+        }
+    }
+
+    Ok(annotated)
+}
+
+async fn zero_poll(state: &AppState, channel: &str) -> Result<bool, TwitchRssError> {
+    #[cfg(feature = "eventsub")]
+    return Ok(crate::eventsub::stored_channel(state, channel)
+        .await?
+        .is_some());
+    #[cfg(not(feature = "eventsub"))]
+    {
+        let _ = (state, channel);
+        Ok(false)
+    }
+}
@@ -0,0 +1,291 @@
+use axum::extract::{OriginalUri, Path, Query, State};
+use futures::future::try_join_all;
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use twitch_api2::helix::clips::Clip;
+use twitch_api2::helix::HelixClient;
+
+use crate::feed::{self, Feed, FeedItem, FeedNaming};
+use crate::i18n::Locale;
+use crate::options;
+use crate::render::Rendered;
+use crate::{get_token, get_user_clips, get_user_id, login, provider, render_feed};
+use crate::{AppState, TwitchRssError};
+
+/// Every channel costs two Helix requests, so fewer are accepted than for live status
+const MAX_NAMES: usize = 25;
+
+/// How far back clips are considered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipPeriod {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl ClipPeriod {
+    pub const ALL: [Self; 4] = [Self::Day, Self::Week, Self::Month, Self::All];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::All => "all",
+        }
+    }
+
+    /// Start of the period, `None` for all time.
+    pub fn started_at(self) -> Option<OffsetDateTime> {
+        let length = match self {
+            Self::Day => time::Duration::days(1),
+            Self::Week => time::Duration::weeks(1),
+            Self::Month => time::Duration::days(30),
+            Self::All => return None,
+        };
+        Some(OffsetDateTime::now_utc() - length)
+    }
+
+    fn parse(period: &str) -> Result<Self, TwitchRssError> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.as_str() == period)
+            .ok_or_else(|| {
+                TwitchRssError::InvalidQuery(format!(
+                    "period={:?} is not one of day, week, month or all",
+                    period
+                ))
+            })
+    }
+}
+
+/// `/channel/{name}/clips`, the most viewed clips of the last week or the requested window.
+pub async fn channel(
+    Path(name): Path<String>,
+    Query(query): Query<WindowQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let window = ClipWindow::parse(&query)?;
+    let locale = query.locale(state.config.locale)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name.as_str().into(),
+    )
+    .await?;
+
+    let ttl = provider::videos_ttl(&state, &name).await;
+
+    let mut clips =
+        get_user_clips(&state.cache, ttl, &helix_client, &token, user_id, window).await?;
+    clips.sort_by_key(|c| std::cmp::Reverse(c.view_count));
+
+    let mut feed = Feed {
+        title: locale.clips_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}/clips", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(ttl),
+        items: clips
+            .iter()
+            .map(|c| feed::clip_to_item(c, locale))
+            .collect(),
+    };
+    feed.apply_templates(&state.config.channel, &FeedNaming::twitch(&name, "clips"));
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// Which clips are requested: those of a rolling period, or of fixed bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipWindow {
+    Period(ClipPeriod),
+    /// Helix ends ranges without an end a week after their start
+    Range {
+        started_at: OffsetDateTime,
+        ended_at: Option<OffsetDateTime>,
+    },
+}
+
+impl ClipWindow {
+    /// Identifies the window in cache keys.
+    pub fn key(self) -> String {
+        match self {
+            Self::Period(period) => period.as_str().to_string(),
+            Self::Range {
+                started_at,
+                ended_at,
+            } => format!(
+                "{}..{}",
+                started_at.unix_timestamp(),
+                ended_at
+                    .map(|t| t.unix_timestamp().to_string())
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    /// `started_at` and `ended_at` of the Helix request.
+    pub fn bounds(self) -> (Option<OffsetDateTime>, Option<OffsetDateTime>) {
+        match self {
+            Self::Period(period) => (period.started_at(), None),
+            Self::Range {
+                started_at,
+                ended_at,
+            } => (Some(started_at), ended_at),
+        }
+    }
+
+    /// A week unless the query asks for another period or for explicit bounds.
+    pub fn parse(query: &WindowQuery) -> Result<Self, TwitchRssError> {
+        let started_at = query
+            .started_at
+            .as_deref()
+            .map(|t| parse_timestamp("started_at", t))
+            .transpose()?;
+        let ended_at = query
+            .ended_at
+            .as_deref()
+            .map(|t| parse_timestamp("ended_at", t))
+            .transpose()?;
+
+        match (query.period.as_deref(), started_at, ended_at) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(TwitchRssError::InvalidQuery(
+                "period can't be combined with started_at or ended_at".to_string(),
+            )),
+            (Some(period), None, None) => ClipPeriod::parse(period).map(Self::Period),
+            (None, None, None) => Ok(Self::Period(ClipPeriod::Week)),
+            (None, None, Some(_)) => Err(TwitchRssError::InvalidQuery(
+                "ended_at needs a started_at".to_string(),
+            )),
+            (None, Some(started_at), Some(ended_at)) if ended_at <= started_at => Err(
+                TwitchRssError::InvalidQuery("ended_at has to be after started_at".to_string()),
+            ),
+            (None, Some(started_at), ended_at) => Ok(Self::Range {
+                started_at,
+                ended_at,
+            }),
+        }
+    }
+}
+
+fn parse_timestamp(name: &str, value: &str) -> Result<OffsetDateTime, TwitchRssError> {
+    OffsetDateTime::parse(value, &Rfc3339).map_err(|e| {
+        TwitchRssError::InvalidQuery(format!(
+            "{}={:?} is not an RFC 3339 timestamp: {}",
+            name, value, e
+        ))
+    })
+}
+
+/// Query parameters selecting a [`ClipWindow`], and the language of clip feeds.
+#[derive(Deserialize)]
+pub struct WindowQuery {
+    period: Option<String>,
+    started_at: Option<String>,
+    ended_at: Option<String>,
+    locale: Option<String>,
+}
+
+impl WindowQuery {
+    /// The requested locale, or `default`.
+    pub fn locale(&self, default: Locale) -> Result<Locale, TwitchRssError> {
+        Ok(options::locale(self.locale.as_deref())?.unwrap_or(default))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ClipsQuery {
+    /// Comma separated logins
+    names: String,
+    #[serde(flatten)]
+    window: WindowQuery,
+}
+
+/// `/channels/clips?names=a,b,c&period=week`, the most viewed clips of several channels. Takes the
+/// same window parameters as [`channel`].
+pub async fn multi(
+    Query(query): Query<ClipsQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let logins = login::parse_logins(&query.names, MAX_NAMES)?;
+    let window = ClipWindow::parse(&query.window)?;
+    let locale = query.window.locale(state.config.locale)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let clips = try_join_all(logins.iter().map(|login| async {
+        let user_id = get_user_id(
+            &state.cache,
+            state.config.user_id_ttl,
+            &helix_client,
+            &token,
+            login.as_str().into(),
+        )
+        .await?;
+
+        get_user_clips(
+            &state.cache,
+            state.config.videos_ttl,
+            &helix_client,
+            &token,
+            user_id,
+            window,
+        )
+        .await
+    }))
+    .await?;
+
+    let mut clips = clips.into_iter().flatten().collect::<Vec<_>>();
+    clips.sort_by_key(|c| std::cmp::Reverse(c.view_count));
+
+    let feed = Feed {
+        title: locale.clips_title(&logins.join(", "), "Twitch"),
+        link: "https://www.twitch.tv/directory".to_string(),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.videos_ttl),
+        items: clips.iter().map(|c| attributed_item(c, locale)).collect(),
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// A clip item whose title says which channel it is from.
+pub fn attributed_item(clip: &Clip, locale: Locale) -> FeedItem {
+    let item = feed::clip_to_item(clip, locale);
+    FeedItem {
+        title: format!("{}: {}", clip.broadcaster_name, item.title),
+        ..item
+    }
+}
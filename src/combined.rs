@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use axum::extract::{OriginalUri, Path, Query, State};
+use twitch_api2::helix::HelixClient;
+
+use crate::clips::{ClipPeriod, ClipWindow};
+use crate::feed::{self, Feed, FeedItem, FeedNaming};
+use crate::options::{FeedOptions, FeedQuery};
+use crate::render::Rendered;
+use crate::{get_token, get_user_clips, get_user_id, login, provider};
+use crate::{render_feed, AppState, TwitchRssError};
+
+/// `/channel/{name}/all`, VODs and clips in a single feed.
+pub async fn all(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name.as_str().into(),
+    )
+    .await?;
+
+    let ttl = provider::videos_ttl(&state, &name).await;
+
+    let (videos, clips) = tokio::try_join!(
+        provider::twitch_videos(&state, &name, &options.videos),
+        get_user_clips(
+            &state.cache,
+            ttl,
+            &helix_client,
+            &token,
+            user_id,
+            ClipWindow::Period(ClipPeriod::Week)
+        ),
+    )?;
+
+    // a clip of a VOD that is already in the feed is just noise next to it
+    let video_ids = videos.iter().map(|v| &v.id).collect::<HashSet<_>>();
+    let locale = options.items.locale.unwrap_or(state.config.locale);
+
+    let items = provider::twitch_items(&state, &name, &videos, &options.items)
+        .into_iter()
+        .map(|i| label(locale.vod_label(), i))
+        .chain(
+            clips
+                .iter()
+                .filter(|c| !video_ids.contains(&c.video_id))
+                .map(|c| label(locale.clip_label(), feed::clip_to_item(c, locale))),
+        )
+        .collect::<Vec<_>>();
+
+    let mut feed = Feed {
+        title: locale.vods_and_clips_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(ttl),
+        items: provider::finish_items(&state, &options, items).await,
+    };
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming::twitch(&name, "VODs and clips"),
+    );
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+fn label(kind: &str, item: FeedItem) -> FeedItem {
+    FeedItem {
+        title: format!("[{}] {}", kind, item.title),
+        ..item
+    }
+}
@@ -0,0 +1,161 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::body::{self, Body};
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use time::format_description::well_known::Rfc2822;
+use time::{OffsetDateTime, UtcOffset};
+
+/// `at` as the value of a `Last-Modified` header, the IMF-fixdate of RFC 9110 like
+/// `Sun, 06 Nov 1994 08:49:37 GMT`. That is RFC 2822 in UTC, with the zone spelled `GMT`.
+pub fn http_date(at: OffsetDateTime) -> Option<String> {
+    let utc = at.to_offset(UtcOffset::UTC).format(&Rfc2822).ok()?;
+    Some(format!("{} GMT", utc.strip_suffix(" +0000")?))
+}
+
+/// RFC 2822 reads the `GMT` of IMF-fixdates too.
+fn parse_http_date(value: &str) -> Option<OffsetDateTime> {
+    OffsetDateTime::parse(value.trim(), &Rfc2822).ok()
+}
+
+/// Add an `ETag` to successful responses and answer conditional requests for an unchanged document
+/// with a 304, so readers polling an unchanged feed don't download it again. `If-None-Match` is
+/// compared with the `ETag`, or failing that `If-Modified-Since` with the `Last-Modified` the
+/// renderer set.
+///
+/// The tag is a hash of the body. It only has to match between responses of the same build, which
+/// the standard library's hash does.
+pub async fn not_modified(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let if_modified_since = request.headers().get(header::IF_MODIFIED_SINCE).cloned();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let tag = tag(&bytes);
+    if let Ok(value) = HeaderValue::from_str(&tag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+
+    // a client sending both trusts the tag, which also changes within the same second
+    let unchanged = match (if_none_match, if_modified_since) {
+        (Some(v), _) => matches(&v, &tag),
+        (None, Some(since)) => unmodified_since(&since, &parts.headers),
+        (None, None) => false,
+    };
+    if unchanged {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        copy_validators(&parts.headers, not_modified.headers_mut());
+        return not_modified;
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn tag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Whether `If-None-Match` lists `tag`. Weak comparison, as RFC 9110 asks for this header, so a
+/// `W/` prefix added by a proxy that compressed the feed still matches.
+fn matches(if_none_match: &HeaderValue, tag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|t| t == "*" || t.trim_start_matches("W/") == tag)
+}
+
+/// Whether the document with `headers` hasn't changed since `if_modified_since`. Documents without
+/// a `Last-Modified` always count as changed.
+fn unmodified_since(if_modified_since: &HeaderValue, headers: &HeaderMap) -> bool {
+    let date = |value: Option<&HeaderValue>| {
+        value
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_http_date)
+    };
+
+    match (
+        date(Some(if_modified_since)),
+        date(headers.get(header::LAST_MODIFIED)),
+    ) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+/// A 304 carries the headers a 200 would have that describe caching.
+fn copy_validators(from: &HeaderMap, to: &mut HeaderMap) {
+    for name in [
+        header::ETAG,
+        header::LAST_MODIFIED,
+        header::VARY,
+        header::CACHE_CONTROL,
+    ] {
+        if let Some(value) = from.get(&name) {
+            to.insert(name, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn header(value: &str) -> HeaderValue {
+        HeaderValue::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn matches_listed_tags() {
+        let tag = tag(b"<rss></rss>");
+
+        assert!(matches(&header(&tag), &tag));
+        assert!(matches(&header(&format!("\"other\", W/{}", tag)), &tag));
+        assert!(matches(&header("*"), &tag));
+        assert!(!matches(&header("\"other\""), &tag));
+        assert_ne!(super::tag(b"<rss></rss>"), super::tag(b"<rss> </rss>"));
+    }
+
+    #[test]
+    fn compares_dates() {
+        let modified = datetime!(1994-11-06 08:49:37.5 UTC);
+        assert_eq!(
+            http_date(modified).as_deref(),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::LAST_MODIFIED, header(&http_date(modified).unwrap()));
+        assert!(unmodified_since(
+            &header("Sun, 06 Nov 1994 08:49:37 GMT"),
+            &headers
+        ));
+        assert!(!unmodified_since(
+            &header("Sun, 06 Nov 1994 08:49:36 GMT"),
+            &headers
+        ));
+        assert!(!unmodified_since(&header("yesterday"), &headers));
+        assert!(!unmodified_since(
+            &header("Sun, 06 Nov 1994 08:49:37 GMT"),
+            &HeaderMap::new()
+        ));
+    }
+}
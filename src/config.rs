@@ -0,0 +1,981 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+use twitch_api2::twitch_oauth2::{ClientId, ClientSecret};
+
+use crate::crawlers;
+use crate::feed::{self, PubDate};
+use crate::i18n::Locale;
+use crate::render::GuidStyle;
+use crate::timezone::TimeZone;
+
+/// Exit status used when the configuration is invalid (`EX_CONFIG` from sysexits.h).
+pub const EXIT_CONFIG: i32 = 78;
+
+/// Settings whose values are never shown
+const SECRETS: &[&str] = &[
+    "TWITCH_CLIENT_SECRET",
+    "ADMIN_TOKEN",
+    "EVENTSUB_SECRET",
+    "CACHE_REDIS_URL",
+];
+
+/// Settings read from the environment.
+#[derive(Clone)]
+pub struct Config {
+    #[cfg(not(feature = "lambda"))]
+    pub port: u16,
+    pub client_id: ClientId,
+    pub client_secret: ClientSecret,
+    pub user_id_ttl: Duration,
+    pub videos_ttl: Duration,
+    pub live_ttl: Duration,
+    /// How long team member lists are cached, they change rarely
+    pub team_ttl: Duration,
+    /// How long stream schedules are cached
+    pub schedule_ttl: Duration,
+    /// How long lists of more than one page of videos are cached
+    pub archive_ttl: Duration,
+    /// How long whole responses of feeds are kept, if at all
+    pub response_ttl: Option<Duration>,
+    /// How long after that a kept response is still served while it's rendered again
+    pub response_stale: Duration,
+    /// Redis the cache is kept in instead of memory
+    pub redis: Option<RedisConfig>,
+    /// Most pages of videos fetched for a single feed
+    pub max_video_pages: usize,
+    pub http: HttpConfig,
+    pub robots_txt: String,
+    pub noindex: bool,
+    /// Leave out videos that aren't public or still being processed unless `public=0`
+    pub public_only: bool,
+    /// Append the length of items to their titles unless `title_duration=0`
+    pub title_duration: bool,
+    /// Timestamp videos are dated with unless `pub_date` says otherwise
+    pub pub_date: PubDate,
+    /// Leave thumbnails out of video items unless `no_thumb=0`
+    pub no_thumbnails: bool,
+    /// Language of generated text unless `locale` says otherwise
+    pub locale: Locale,
+    /// Time zone of dates in descriptions unless `tz` says otherwise
+    pub time_zone: TimeZone,
+    /// Sites the player is embedded for with `embed=1`, the `parent` Twitch requires
+    pub embed_parents: Vec<String>,
+    pub short_routes: bool,
+    /// Public URL of this server, without a trailing slash
+    pub base_url: Option<String>,
+    pub channel: ChannelMetadata,
+    pub channel_overrides_file: Option<PathBuf>,
+    #[cfg(feature = "admin")]
+    pub admin_token: Option<String>,
+    #[cfg(feature = "admin")]
+    pub tracked_channels_file: Option<PathBuf>,
+    #[cfg(feature = "templates")]
+    pub template_dir: Option<PathBuf>,
+    #[cfg(feature = "scripting")]
+    pub item_script: Option<PathBuf>,
+    #[cfg(feature = "eventsub")]
+    pub eventsub: Option<EventSubConfig>,
+    #[cfg(all(feature = "http3", not(feature = "lambda")))]
+    pub http3: Option<Http3Config>,
+    #[cfg(feature = "media")]
+    pub media: Option<MediaConfig>,
+    /// Raw values of the variables that were set, for [`Config::effective`]
+    from_env: BTreeMap<&'static str, String>,
+}
+
+/// Where `CACHE_REDIS_URL` points.
+#[derive(Clone)]
+pub struct RedisConfig {
+    /// `host:port`
+    pub address: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Database number, 0 unless the URL has a path
+    pub db: u32,
+}
+
+#[cfg(feature = "eventsub")]
+#[derive(Clone)]
+pub struct EventSubConfig {
+    /// Public URL of `/eventsub/callback`
+    pub callback_url: String,
+    pub secret: String,
+    /// Serve tracked channels only from what notifications and the backfill stored
+    pub zero_poll: bool,
+    /// Add an item to VOD feeds whenever a tracked channel goes live
+    pub live_items: bool,
+}
+
+#[cfg(feature = "media")]
+#[derive(Clone)]
+pub struct MediaConfig {
+    /// Program and arguments, run with the URL of a video appended
+    pub command: Vec<String>,
+    /// How long a request waits for media, and a single resolver may run
+    pub timeout: Duration,
+    /// How long resolved media URLs are cached
+    pub ttl: Duration,
+}
+
+/// How long requests wait for a media resolver by default.
+#[cfg(feature = "media")]
+const MEDIA_RESOLVER_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Twitch's playlist URLs stop working after a while, so they aren't kept for long by default.
+#[cfg(feature = "media")]
+const MEDIA_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Optional elements added to every generated channel.
+#[derive(Clone, Default)]
+pub struct ChannelMetadata {
+    pub copyright: Option<String>,
+    pub generator: Option<String>,
+    /// Technical contact, an email address optionally followed by a name in parentheses
+    pub web_master: Option<String>,
+    /// Editorial contact, in the same format as `web_master`
+    pub managing_editor: Option<String>,
+    /// URL of an icon representing this server, for formats that show one next to the channel's
+    pub icon: Option<String>,
+    /// Title of channel feeds, with [`feed::FEED_PLACEHOLDERS`] filled in
+    pub title_template: Option<String>,
+    /// Description of channel feeds, in the same format as `title_template`
+    pub description_template: Option<String>,
+    /// What RSS item guids are made of
+    pub guid: GuidStyle,
+    /// WebSub hub advertised by feeds that link to themselves
+    pub hub: Option<String>,
+}
+
+#[cfg(all(feature = "http3", not(feature = "lambda")))]
+#[derive(Clone)]
+pub struct Http3Config {
+    /// UDP port of the QUIC listener
+    pub port: u16,
+    /// PEM certificate chain
+    pub cert_file: PathBuf,
+    /// PEM private key
+    pub key_file: PathBuf,
+}
+
+#[derive(Clone, Default)]
+pub struct HttpConfig {
+    pub pool_max_idle_per_host: Option<usize>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub tcp_keepalive: Option<Duration>,
+    pub http2_prior_knowledge: bool,
+}
+
+/// A resolved setting and where its value came from.
+#[derive(Serialize)]
+pub struct Setting {
+    pub name: &'static str,
+    pub value: String,
+    /// `env` or `default`
+    pub source: &'static str,
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={} ({})", self.name, self.value, self.source)
+    }
+}
+
+/// A single problem with the configuration.
+pub struct ConfigError {
+    pub field: &'static str,
+    pub problem: String,
+    pub hint: &'static str,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.field, self.problem, self.hint)
+    }
+}
+
+impl ConfigError {
+    /// Record the outcome of loading something the configuration points at.
+    pub fn check<T, E: fmt::Display>(
+        errors: &mut Vec<ConfigError>,
+        field: &'static str,
+        hint: &'static str,
+        result: Result<T, E>,
+    ) -> Option<T> {
+        match result {
+            Ok(v) => Some(v),
+            Err(e) => {
+                errors.push(ConfigError {
+                    field,
+                    problem: format!("{}", e),
+                    hint,
+                });
+                None
+            }
+        }
+    }
+}
+
+/// Print every problem and exit with [`EXIT_CONFIG`].
+pub fn exit_with(errors: Vec<ConfigError>) -> ! {
+    eprintln!("invalid configuration:");
+    for e in errors {
+        eprintln!("  {}", e);
+    }
+    std::process::exit(EXIT_CONFIG)
+}
+
+impl Config {
+    /// Read every setting, collecting all problems instead of stopping at the first one.
+    pub fn from_env() -> Result<Self, Vec<ConfigError>> {
+        match Self::read_env() {
+            (config, errors) if errors.is_empty() => Ok(config),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    /// [`Config::from_env`] that returns the configuration along with its problems, defaults
+    /// standing in for the settings that have one. The files and directories it points at can
+    /// then still be checked, so they are reported in the same run.
+    pub fn read_env() -> (Self, Vec<ConfigError>) {
+        Self::read(Reader::default())
+    }
+
+    fn read(mut r: Reader) -> (Self, Vec<ConfigError>) {
+        let config = Config {
+            #[cfg(not(feature = "lambda"))]
+            port: r
+                .required("PORT", "the port to listen on, e.g. 8080")
+                .unwrap_or_default(),
+            client_id: r
+                .required::<String>(
+                    "TWITCH_CLIENT_ID",
+                    "client id of an application registered at dev.twitch.tv",
+                )
+                .unwrap_or_default()
+                .into(),
+            client_secret: r
+                .required::<String>(
+                    "TWITCH_CLIENT_SECRET",
+                    "client secret of the same application",
+                )
+                .unwrap_or_default()
+                .into(),
+            user_id_ttl: r.ttl("USER_ID_CACHE_TTL", Duration::from_secs(600)),
+            videos_ttl: r.ttl("VIDEOS_CACHE_TTL", Duration::from_secs(600)),
+            live_ttl: r.ttl("LIVE_CACHE_TTL", Duration::from_secs(60)),
+            team_ttl: r.ttl("TEAM_CACHE_TTL", Duration::from_secs(3600)),
+            schedule_ttl: r.ttl("SCHEDULE_CACHE_TTL", Duration::from_secs(3600)),
+            archive_ttl: r.ttl("ARCHIVE_CACHE_TTL", Duration::from_secs(86400)),
+            response_ttl: r
+                .optional(
+                    "RESPONSE_CACHE_TTL",
+                    "a number of seconds, 0 to turn it off",
+                )
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            response_stale: Duration::from_secs(
+                r.optional(
+                    "RESPONSE_STALE_TTL",
+                    "a number of seconds, 0 to turn it off",
+                )
+                .unwrap_or(3600),
+            ),
+            redis: r.redis("CACHE_REDIS_URL"),
+            max_video_pages: r
+                .optional("MAX_VIDEO_PAGES", "a number of pages")
+                .unwrap_or(10),
+            http: HttpConfig {
+                pool_max_idle_per_host: r
+                    .optional("HTTP_POOL_MAX_IDLE_PER_HOST", "a number of connections"),
+                pool_idle_timeout: r
+                    .optional("HTTP_POOL_IDLE_TIMEOUT", "a number of seconds")
+                    .map(Duration::from_secs),
+                tcp_keepalive: r
+                    .optional("HTTP_TCP_KEEPALIVE", "a number of seconds")
+                    .map(Duration::from_secs),
+                http2_prior_knowledge: r
+                    .optional("HTTP2_PRIOR_KNOWLEDGE", "true or false")
+                    .unwrap_or(false),
+            },
+            robots_txt: r
+                .file_contents("ROBOTS_TXT", "path to a robots.txt file")
+                .unwrap_or_else(|| crawlers::DEFAULT_ROBOTS_TXT.to_string()),
+            noindex: r.optional("NOINDEX", "true or false").unwrap_or(false),
+            public_only: r.optional("PUBLIC_ONLY", "true or false").unwrap_or(false),
+            title_duration: r
+                .optional("TITLE_DURATION", "true or false")
+                .unwrap_or(false),
+            pub_date: r
+                .optional("PUB_DATE", "created_at or published_at")
+                .unwrap_or_default(),
+            no_thumbnails: r
+                .optional("NO_THUMBNAILS", "true or false")
+                .unwrap_or(false),
+            locale: r
+                .optional("FEED_LOCALE", "one of en, de, fr, es")
+                .unwrap_or_default(),
+            time_zone: r.time_zone("FEED_TIMEZONE"),
+            embed_parents: r.domains("EMBED_PARENT", "comma separated domains like example.com"),
+            short_routes: r.optional("SHORT_ROUTES", "true or false").unwrap_or(true),
+            base_url: r.public_url("BASE_URL", "the http(s) URL this server is reachable at"),
+            channel: ChannelMetadata {
+                copyright: r.optional("FEED_COPYRIGHT", ""),
+                generator: r.optional("FEED_GENERATOR", ""),
+                web_master: r.optional("FEED_WEBMASTER", ""),
+                managing_editor: r.optional("FEED_MANAGING_EDITOR", ""),
+                icon: r.optional("FEED_ICON", ""),
+                title_template: r.template("FEED_TITLE"),
+                description_template: r.template("FEED_DESCRIPTION"),
+                guid: r
+                    .optional("ITEM_GUID", "id, url or login_id")
+                    .unwrap_or_default(),
+                hub: r.public_url("WEBSUB_HUB", "the http(s) URL of a WebSub hub"),
+            },
+            channel_overrides_file: r.existing_path(
+                "CHANNEL_OVERRIDES_FILE",
+                false,
+                "a JSON file of per-channel settings",
+            ),
+            #[cfg(feature = "admin")]
+            admin_token: r
+                .optional::<String>("ADMIN_TOKEN", "")
+                .filter(|t| !t.is_empty()),
+            #[cfg(feature = "admin")]
+            tracked_channels_file: r.optional("TRACKED_CHANNELS_FILE", ""),
+            #[cfg(feature = "templates")]
+            template_dir: r.existing_path(
+                "TEMPLATE_DIR",
+                true,
+                "a directory containing template files",
+            ),
+            #[cfg(feature = "scripting")]
+            item_script: r.existing_path("ITEM_SCRIPT", false, "path to a Rhai script"),
+            #[cfg(feature = "eventsub")]
+            eventsub: r.eventsub(),
+            #[cfg(all(feature = "http3", not(feature = "lambda")))]
+            http3: r.http3(),
+            #[cfg(feature = "media")]
+            media: r.media(),
+            from_env: BTreeMap::new(),
+        };
+
+        // hubs are told about feeds by their public URL, which only BASE_URL knows
+        if config.channel.hub.is_some() && config.base_url.is_none() {
+            r.error(
+                "WEBSUB_HUB",
+                "needs BASE_URL",
+                "the URL this server is reachable at, for the hub to fetch feeds from",
+            );
+        }
+        // the hub is only pinged on EventSub notifications, without them subscribers never hear
+        #[cfg(feature = "eventsub")]
+        let eventsub = config.eventsub.is_some();
+        #[cfg(not(feature = "eventsub"))]
+        let eventsub = false;
+        if config.channel.hub.is_some() && !eventsub {
+            r.error(
+                "WEBSUB_HUB",
+                "needs EventSub",
+                "set EVENTSUB_CALLBACK_URL and EVENTSUB_SECRET, in a build with --features eventsub",
+            );
+        }
+
+        // twitch_api2 reads these itself and panics on the first request if they don't parse
+        #[cfg(feature = "mock_api")]
+        {
+            r.base_url("TWITCH_HELIX_URL", "e.g. http://localhost:8080/mock/");
+            r.base_url("TWITCH_OAUTH2_URL", "e.g. http://localhost:8080/auth/");
+        }
+
+        (
+            Config {
+                from_env: r.from_env,
+                ..config
+            },
+            r.errors,
+        )
+    }
+
+    /// Absolute URL of `path` (with an optional query) on this server, if `BASE_URL` is set.
+    pub fn public_url(&self, path: &str) -> Option<String> {
+        self.base_url
+            .as_ref()
+            .map(|base| format!("{}/{}", base, path.trim_start_matches('/')))
+    }
+
+    /// Every setting with its effective value, secrets redacted.
+    pub fn effective(&self) -> Vec<Setting> {
+        let mut settings = Vec::new();
+        let mut add = |name: &'static str, default: String| {
+            let (value, source) = match self.from_env.get(name) {
+                Some(_) if SECRETS.contains(&name) => ("<redacted>".to_string(), "env"),
+                Some(raw) => (raw.clone(), "env"),
+                None => (default, "default"),
+            };
+            settings.push(Setting {
+                name,
+                value,
+                source,
+            });
+        };
+        let unset = String::new;
+        let secs = |d: Duration| d.as_secs().to_string();
+
+        #[cfg(not(feature = "lambda"))]
+        add("PORT", self.port.to_string());
+        add("TWITCH_CLIENT_ID", unset());
+        add("TWITCH_CLIENT_SECRET", unset());
+        add("USER_ID_CACHE_TTL", secs(self.user_id_ttl));
+        add("VIDEOS_CACHE_TTL", secs(self.videos_ttl));
+        add("LIVE_CACHE_TTL", secs(self.live_ttl));
+        add("TEAM_CACHE_TTL", secs(self.team_ttl));
+        add("SCHEDULE_CACHE_TTL", secs(self.schedule_ttl));
+        add("ARCHIVE_CACHE_TTL", secs(self.archive_ttl));
+        add(
+            "RESPONSE_CACHE_TTL",
+            secs(self.response_ttl.unwrap_or_default()),
+        );
+        add("RESPONSE_STALE_TTL", secs(self.response_stale));
+        add("CACHE_REDIS_URL", unset());
+        add("MAX_VIDEO_PAGES", self.max_video_pages.to_string());
+        add("HTTP_POOL_MAX_IDLE_PER_HOST", unset());
+        add("HTTP_POOL_IDLE_TIMEOUT", unset());
+        add("HTTP_TCP_KEEPALIVE", unset());
+        add(
+            "HTTP2_PRIOR_KNOWLEDGE",
+            self.http.http2_prior_knowledge.to_string(),
+        );
+        add("ROBOTS_TXT", "built-in".to_string());
+        add("NOINDEX", self.noindex.to_string());
+        add("PUBLIC_ONLY", self.public_only.to_string());
+        add("TITLE_DURATION", self.title_duration.to_string());
+        add("PUB_DATE", self.pub_date.as_str().to_string());
+        add("NO_THUMBNAILS", self.no_thumbnails.to_string());
+        add("FEED_LOCALE", self.locale.as_str().to_string());
+        add("FEED_TIMEZONE", self.time_zone.name().to_string());
+        add("EMBED_PARENT", unset());
+        add("SHORT_ROUTES", self.short_routes.to_string());
+        add("BASE_URL", unset());
+        add("FEED_COPYRIGHT", unset());
+        add("FEED_GENERATOR", unset());
+        add("FEED_WEBMASTER", unset());
+        add("FEED_MANAGING_EDITOR", unset());
+        add("FEED_ICON", unset());
+        add("FEED_TITLE", unset());
+        add("FEED_DESCRIPTION", unset());
+        add("ITEM_GUID", self.channel.guid.as_str().to_string());
+        add("WEBSUB_HUB", unset());
+        add("CHANNEL_OVERRIDES_FILE", unset());
+        #[cfg(feature = "admin")]
+        {
+            add("ADMIN_TOKEN", unset());
+            add("TRACKED_CHANNELS_FILE", unset());
+        }
+        #[cfg(feature = "templates")]
+        add("TEMPLATE_DIR", unset());
+        #[cfg(feature = "scripting")]
+        add("ITEM_SCRIPT", unset());
+        #[cfg(feature = "eventsub")]
+        {
+            add("EVENTSUB_CALLBACK_URL", unset());
+            add("EVENTSUB_SECRET", unset());
+            add(
+                "EVENTSUB_ZERO_POLL",
+                self.eventsub
+                    .as_ref()
+                    .is_some_and(|e| e.zero_poll)
+                    .to_string(),
+            );
+            add(
+                "EVENTSUB_LIVE_ITEMS",
+                self.eventsub
+                    .as_ref()
+                    .is_some_and(|e| e.live_items)
+                    .to_string(),
+            );
+        }
+        #[cfg(feature = "media")]
+        {
+            add("MEDIA_RESOLVER", unset());
+            let media = self.media.as_ref();
+            add(
+                "MEDIA_RESOLVER_TIMEOUT",
+                secs(media.map_or(MEDIA_RESOLVER_TIMEOUT, |m| m.timeout)),
+            );
+            add(
+                "MEDIA_CACHE_TTL",
+                secs(media.map_or(MEDIA_CACHE_TTL, |m| m.ttl)),
+            );
+        }
+        #[cfg(feature = "mock_api")]
+        {
+            add(
+                "TWITCH_HELIX_URL",
+                "https://api.twitch.tv/helix/".to_string(),
+            );
+            add(
+                "TWITCH_OAUTH2_URL",
+                "https://id.twitch.tv/oauth2/".to_string(),
+            );
+        }
+
+        settings
+    }
+}
+
+#[derive(Default)]
+struct Reader {
+    errors: Vec<ConfigError>,
+    from_env: BTreeMap<&'static str, String>,
+    /// Read instead of the process environment when set
+    vars: Option<BTreeMap<&'static str, String>>,
+}
+
+impl Reader {
+    fn lookup(&self, field: &'static str) -> Result<String, env::VarError> {
+        match &self.vars {
+            Some(vars) => vars.get(field).cloned().ok_or(env::VarError::NotPresent),
+            None => env::var(field),
+        }
+    }
+
+    fn error(&mut self, field: &'static str, problem: impl Into<String>, hint: &'static str) {
+        self.errors.push(ConfigError {
+            field,
+            problem: problem.into(),
+            hint,
+        });
+    }
+
+    /// Read `field`, remembering the raw value for the config dump.
+    fn var(&mut self, field: &'static str) -> Option<String> {
+        match self.lookup(field) {
+            Ok(value) => {
+                self.from_env.insert(field, value.clone());
+                Some(value)
+            }
+            Err(env::VarError::NotPresent) => None,
+            Err(env::VarError::NotUnicode(_)) => {
+                self.error(field, "is not valid UTF-8", "");
+                None
+            }
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, field: &'static str, hint: &'static str) -> Option<T> {
+        let value = self.var(field)?;
+        match value.parse() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                self.error(field, format!("{:?} is not a valid value", value), hint);
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, field: &'static str, hint: &'static str) -> Option<T> {
+        if let Err(env::VarError::NotPresent) = self.lookup(field) {
+            self.error(field, "is not set", hint);
+            return None;
+        }
+        self.optional(field, hint)
+    }
+
+    /// An absolute http(s) URL without query or fragment, trailing slashes removed. The path is
+    /// kept for servers reachable under a prefix.
+    fn public_url(&mut self, field: &'static str, hint: &'static str) -> Option<String> {
+        let value = self.var(field)?;
+        match reqwest::Url::parse(&value) {
+            Ok(url)
+                if matches!(url.scheme(), "http" | "https")
+                    && url.has_host()
+                    && url.query().is_none()
+                    && url.fragment().is_none() =>
+            {
+                Some(url.as_str().trim_end_matches('/').to_string())
+            }
+            _ => {
+                self.error(field, format!("{:?} is not an http(s) URL", value), hint);
+                None
+            }
+        }
+    }
+
+    /// A `redis://[[user]:password@]host[:port][/db]` URL. The value isn't repeated in errors, it
+    /// usually carries a password.
+    fn redis(&mut self, field: &'static str) -> Option<RedisConfig> {
+        const HINT: &str = "like redis://:password@localhost:6379/0";
+        let value = self.var(field)?;
+        let url = match reqwest::Url::parse(&value) {
+            Ok(url) if url.scheme() == "redis" && url.has_host() => url,
+            Ok(url) if url.scheme() == "rediss" => {
+                self.error(field, "TLS connections aren't supported", HINT);
+                return None;
+            }
+            _ => {
+                self.error(field, "is not a redis:// URL", HINT);
+                return None;
+            }
+        };
+        let db = match url.path().trim_matches('/') {
+            "" => 0,
+            db => match db.parse() {
+                Ok(db) => db,
+                Err(_) => {
+                    self.error(field, format!("{:?} is not a database number", db), HINT);
+                    return None;
+                }
+            },
+        };
+
+        Some(RedisConfig {
+            address: format!(
+                "{}:{}",
+                url.host_str().unwrap_or_default(),
+                url.port().unwrap_or(6379)
+            ),
+            username: Some(url.username().to_string()).filter(|u| !u.is_empty()),
+            password: url.password().map(str::to_string),
+            db,
+        })
+    }
+
+    /// A comma separated list of domain names, empty if `field` isn't set.
+    fn domains(&mut self, field: &'static str, hint: &'static str) -> Vec<String> {
+        let Some(value) = self.var(field) else {
+            return Vec::new();
+        };
+        let domains = value
+            .split(',')
+            .map(|d| d.trim().to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let valid = |d: &String| {
+            d.split('.').all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+        };
+        match domains.iter().all(valid) {
+            true => domains,
+            false => {
+                self.error(field, format!("{:?} is not a list of domains", value), hint);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Text with `{placeholder}`s from [`feed::FEED_PLACEHOLDERS`].
+    fn template(&mut self, field: &'static str) -> Option<String> {
+        let value = self.var(field)?;
+        let values = feed::FEED_PLACEHOLDERS.map(|name| (name, ""));
+        match feed::fill_template(&value, &values) {
+            Ok(_) => Some(value),
+            Err(name) => {
+                self.error(
+                    field,
+                    format!("{{{}}} is not a placeholder", name),
+                    "{login}, {name}, {platform}, {feed} or {description}",
+                );
+                None
+            }
+        }
+    }
+
+    /// A time zone from the system's database, UTC if `field` isn't set.
+    fn time_zone(&mut self, field: &'static str) -> TimeZone {
+        let Some(value) = self.var(field) else {
+            return TimeZone::utc();
+        };
+        match TimeZone::load(&value) {
+            Ok(time_zone) => time_zone,
+            Err(problem) => {
+                self.error(
+                    field,
+                    problem,
+                    "an IANA time zone name, with tzdata installed",
+                );
+                TimeZone::utc()
+            }
+        }
+    }
+
+    fn ttl(&mut self, field: &'static str, default: Duration) -> Duration {
+        match self.optional::<u64>(field, "a number of seconds") {
+            Some(0) => {
+                self.error(field, "must be greater than 0", "a number of seconds");
+                default
+            }
+            Some(secs) => Duration::from_secs(secs),
+            None => default,
+        }
+    }
+
+    #[cfg(feature = "eventsub")]
+    fn eventsub(&mut self) -> Option<EventSubConfig> {
+        const URL_HINT: &str = "public https URL of /eventsub/callback on this server";
+        const SECRET_HINT: &str = "10 to 100 ASCII characters, used to sign notifications";
+
+        let callback_url = self.var("EVENTSUB_CALLBACK_URL");
+        let secret = self.var("EVENTSUB_SECRET");
+        let zero_poll = self
+            .optional("EVENTSUB_ZERO_POLL", "true or false")
+            .unwrap_or(false);
+        let live_items = self
+            .optional("EVENTSUB_LIVE_ITEMS", "true or false")
+            .unwrap_or(false);
+
+        match (callback_url, secret) {
+            (None, None) => None,
+            (Some(_), None) => {
+                self.error("EVENTSUB_SECRET", "is not set", SECRET_HINT);
+                None
+            }
+            (None, Some(_)) => {
+                self.error("EVENTSUB_CALLBACK_URL", "is not set", URL_HINT);
+                None
+            }
+            (Some(callback_url), Some(secret)) => {
+                let mut valid = true;
+                // twitch only delivers to https callbacks on port 443
+                if !callback_url.starts_with("https://") {
+                    self.error(
+                        "EVENTSUB_CALLBACK_URL",
+                        format!("{:?} is not an https URL", callback_url),
+                        URL_HINT,
+                    );
+                    valid = false;
+                }
+                if !(10..=100).contains(&secret.len()) || !secret.is_ascii() {
+                    self.error("EVENTSUB_SECRET", "has an invalid length", SECRET_HINT);
+                    valid = false;
+                }
+                valid.then_some(EventSubConfig {
+                    callback_url,
+                    secret,
+                    zero_poll,
+                    live_items,
+                })
+            }
+        }
+    }
+
+    #[cfg(all(feature = "http3", not(feature = "lambda")))]
+    fn http3(&mut self) -> Option<Http3Config> {
+        const HINT: &str = "PEM file, needed for HTTP3_PORT";
+
+        let port = self.optional("HTTP3_PORT", "the UDP port to listen on, e.g. 443")?;
+        let cert_file = self.existing_path("TLS_CERT_FILE", false, HINT);
+        let key_file = self.existing_path("TLS_KEY_FILE", false, HINT);
+
+        match (cert_file, key_file) {
+            (Some(cert_file), Some(key_file)) => Some(Http3Config {
+                port,
+                cert_file,
+                key_file,
+            }),
+            (cert_file, key_file) => {
+                // existing_path already reported set but unusable paths
+                if cert_file.is_none() && !self.from_env.contains_key("TLS_CERT_FILE") {
+                    self.error("TLS_CERT_FILE", "is not set", HINT);
+                }
+                if key_file.is_none() && !self.from_env.contains_key("TLS_KEY_FILE") {
+                    self.error("TLS_KEY_FILE", "is not set", HINT);
+                }
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "media")]
+    fn media(&mut self) -> Option<MediaConfig> {
+        let timeout = self.ttl("MEDIA_RESOLVER_TIMEOUT", MEDIA_RESOLVER_TIMEOUT);
+        let ttl = self.ttl("MEDIA_CACHE_TTL", MEDIA_CACHE_TTL);
+        let command = self.var("MEDIA_RESOLVER")?;
+        let command = command
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if command.is_empty() {
+            self.error("MEDIA_RESOLVER", "is empty", "a command like yt-dlp -g");
+            return None;
+        }
+
+        Some(MediaConfig {
+            command,
+            timeout,
+            ttl,
+        })
+    }
+
+    #[cfg(feature = "mock_api")]
+    fn base_url(&mut self, field: &'static str, hint: &'static str) {
+        let Some(value) = self.var(field) else {
+            return;
+        };
+        match reqwest::Url::parse(&value) {
+            // paths are joined onto it, without the slash the last segment would be replaced
+            Ok(url) if !url.path().ends_with('/') => {
+                self.error(field, format!("{:?} does not end with '/'", value), hint)
+            }
+            Ok(_) => {}
+            Err(e) => self.error(field, format!("{:?} is not a URL: {}", value, e), hint),
+        }
+    }
+
+    fn file_contents(&mut self, field: &'static str, hint: &'static str) -> Option<String> {
+        let path = PathBuf::from(self.var(field)?);
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(e) => {
+                self.error(field, format!("{}: {}", path.display(), e), hint);
+                None
+            }
+        }
+    }
+
+    fn existing_path(
+        &mut self,
+        field: &'static str,
+        dir: bool,
+        hint: &'static str,
+    ) -> Option<PathBuf> {
+        let path = PathBuf::from(self.var(field)?);
+        match path.metadata() {
+            Ok(meta) if meta.is_dir() == dir => Some(path),
+            Ok(_) if dir => {
+                self.error(
+                    field,
+                    format!("{} is not a directory", path.display()),
+                    hint,
+                );
+                None
+            }
+            Ok(_) => {
+                self.error(field, format!("{} is not a file", path.display()), hint);
+                None
+            }
+            Err(e) => {
+                self.error(field, format!("{}: {}", path.display(), e), hint);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(vars: &[(&'static str, &str)]) -> (Config, Vec<ConfigError>) {
+        let mut vars = vars
+            .iter()
+            .map(|(k, v)| (*k, v.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        for (field, value) in [
+            ("PORT", "8080"),
+            ("TWITCH_CLIENT_ID", "client-id"),
+            ("TWITCH_CLIENT_SECRET", "client-secret"),
+        ] {
+            vars.entry(field).or_insert_with(|| value.to_string());
+        }
+        Config::read(Reader {
+            vars: Some(vars),
+            ..Reader::default()
+        })
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let (_, errors) = read(&[
+            ("VIDEOS_CACHE_TTL", "soon"),
+            ("LIVE_CACHE_TTL", "0"),
+            ("FEED_LOCALE", "it"),
+            ("CACHE_REDIS_URL", "http://localhost:6379"),
+            ("ROBOTS_TXT", "/nonexistent/robots.txt"),
+            ("CHANNEL_OVERRIDES_FILE", "/nonexistent/overrides.json"),
+        ]);
+
+        let fields = errors.iter().map(|e| e.field).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                "VIDEOS_CACHE_TTL",
+                "LIVE_CACHE_TTL",
+                "CACHE_REDIS_URL",
+                "ROBOTS_TXT",
+                "FEED_LOCALE",
+                "CHANNEL_OVERRIDES_FILE",
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "VIDEOS_CACHE_TTL: \"soon\" is not a valid value (a number of seconds)"
+        );
+        assert!(errors[3].problem.starts_with("/nonexistent/robots.txt: "));
+    }
+
+    #[test]
+    fn reports_missing_credentials() {
+        let (_, errors) = Config::read(Reader {
+            vars: Some(BTreeMap::from([("PORT", "8080".to_string())])),
+            ..Reader::default()
+        });
+
+        let fields = errors.iter().map(|e| e.field).collect::<Vec<_>>();
+        assert_eq!(fields, ["TWITCH_CLIENT_ID", "TWITCH_CLIENT_SECRET"]);
+        assert!(errors.iter().all(|e| e.problem == "is not set"));
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let (config, errors) = read(&[
+            ("CACHE_REDIS_URL", "redis://:hunter2@cache:6380/2"),
+            ("ADMIN_TOKEN", "admin-token"),
+        ]);
+        assert!(errors.is_empty());
+
+        let settings = config.effective();
+        let value = |name| {
+            settings
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| s.value.as_str())
+        };
+        assert_eq!(value("TWITCH_CLIENT_ID"), Some("client-id"));
+        assert_eq!(value("TWITCH_CLIENT_SECRET"), Some("<redacted>"));
+        assert_eq!(value("CACHE_REDIS_URL"), Some("<redacted>"));
+        assert_eq!(value("VIDEOS_CACHE_TTL"), Some("600"));
+        for secret in ["client-secret", "hunter2", "admin-token"] {
+            assert!(settings.iter().all(|s| !s.value.contains(secret)));
+        }
+    }
+
+    #[test]
+    fn parses_redis_urls() {
+        let (config, _) = read(&[("CACHE_REDIS_URL", "redis://:hunter2@cache:6380/2")]);
+        let redis = config.redis.unwrap();
+        assert_eq!(redis.address, "cache:6380");
+        assert_eq!(redis.username, None);
+        assert_eq!(redis.password.as_deref(), Some("hunter2"));
+        assert_eq!(redis.db, 2);
+
+        let (config, _) = read(&[("CACHE_REDIS_URL", "redis://user:pw@cache")]);
+        let redis = config.redis.unwrap();
+        assert_eq!(redis.address, "cache:6379");
+        assert_eq!(redis.username.as_deref(), Some("user"));
+        assert_eq!(redis.db, 0);
+
+        for url in ["rediss://cache:6380", "redis://cache/db"] {
+            let (config, errors) = read(&[("CACHE_REDIS_URL", url)]);
+            assert!(config.redis.is_none());
+            assert_eq!(errors.len(), 1, "{}", url);
+            // the URL may carry a password, so it isn't repeated
+            assert!(!errors[0].problem.contains("cache"), "{}", url);
+        }
+    }
+}
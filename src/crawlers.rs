@@ -0,0 +1,76 @@
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::AppState;
+
+/// Served when `ROBOTS_TXT` isn't configured. Feeds are per channel, so letting crawlers walk
+/// them only burns Twitch quota.
+pub const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+
+pub async fn robots_txt(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain")],
+        state.config.robots_txt.clone(),
+    )
+}
+
+/// Files crawlers and vulnerability scanners ask every server for. None of them can be a channel
+/// or a game, but `/game/{name}/...` would look them up.
+const PROBES: [&str; 11] = [
+    "favicon.ico",
+    "apple-touch-icon.png",
+    "apple-touch-icon-precomposed.png",
+    "sitemap.xml",
+    "ads.txt",
+    "security.txt",
+    "wp-login.php",
+    "xmlrpc.php",
+    "wp-admin",
+    "wp-content",
+    "cgi-bin",
+];
+
+/// Whether the path segment `segment` is a crawler or scanner looking for something this server
+/// doesn't have.
+fn is_probe(segment: &str) -> bool {
+    let segment = segment.to_ascii_lowercase();
+    // dotfiles like `.env` and `.git`
+    segment.starts_with('.') || segment.ends_with(".php") || PROBES.contains(&segment.as_str())
+}
+
+/// Answer requests for [`PROBES`] and the like with a 404 before they reach a handler, so bots
+/// don't burn Twitch quota.
+pub async fn reject_probes(request: Request, next: Next) -> Response {
+    if request.uri().path().split('/').any(is_probe) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Ask search engines not to index a response, for `NOINDEX=true`.
+pub async fn noindex(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        header::HeaderName::from_static("x-robots-tag"),
+        HeaderValue::from_static("noindex"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_probes() {
+        assert!(is_probe("favicon.ico"));
+        assert!(is_probe("WP-Login.php"));
+        assert!(is_probe(".env"));
+        assert!(is_probe("config.php"));
+        assert!(!is_probe("vod.atom"));
+        assert!(!is_probe("super mario 64"));
+        assert!(!is_probe(""));
+    }
+}
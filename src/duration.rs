@@ -0,0 +1,61 @@
+/// Seconds in `duration`, a sequence of numbers each followed by `d`, `h`, `m` or `s`.
+///
+/// Helix only uses hours, minutes and seconds, days are accepted for durations given in queries.
+pub fn parse(duration: &str) -> Option<u64> {
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in duration.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let value = number.parse::<u64>().ok()?.checked_mul(unit)?;
+        total = total.checked_add(value)?;
+        number.clear();
+    }
+
+    (number.is_empty() && !duration.is_empty()).then_some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_helix_durations() {
+        assert_eq!(parse("3h12m5s"), Some(3 * 3600 + 12 * 60 + 5));
+        assert_eq!(parse("45m"), Some(45 * 60));
+        assert_eq!(parse("59s"), Some(59));
+        assert_eq!(parse("1h0m0s"), Some(3600));
+    }
+
+    #[test]
+    fn parses_days() {
+        assert_eq!(parse("14d"), Some(14 * 86400));
+        assert_eq!(parse("1d12h"), Some(36 * 3600));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("30"), None);
+        assert_eq!(parse("m"), None);
+        assert_eq!(parse("1h30x"), None);
+        assert_eq!(parse("-5m"), None);
+        assert_eq!(parse("1.5h"), None);
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(parse("99999999999999999999s"), None);
+        assert_eq!(parse("999999999999999999d"), None);
+    }
+}
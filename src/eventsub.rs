@@ -0,0 +1,719 @@
+use std::collections::{HashMap, VecDeque};
+
+use axum::body::Bytes;
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::sync::{Mutex, RwLock};
+use twitch_api2::helix::users::User;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::helix::HelixClient;
+use twitch_api2::twitch_oauth2::TwitchToken;
+use twitch_api2::types::UserId;
+
+use crate::feed::{Feed, FeedItem, FeedNaming};
+use crate::i18n::Locale;
+use crate::options::{self, LocaleQuery, VideoFilter};
+use crate::timezone::TimeZone;
+use crate::{cache, get_token, get_user, get_user_id, get_user_videos, login, render_feed, websub};
+use crate::{AppState, TwitchRssError};
+
+/// Subscription types (and versions) created for every tracked channel
+const SUBSCRIPTIONS: &[(&str, &str)] = &[
+    ("channel.update", "2"),
+    ("stream.online", "1"),
+    ("stream.offline", "1"),
+];
+
+/// Notifications older (or more in the future) than this are rejected to prevent replays
+const MAX_MESSAGE_AGE: time::Duration = time::Duration::minutes(10);
+/// How many message ids are remembered to drop duplicate deliveries
+const SEEN_MESSAGES: usize = 1000;
+/// How many changes are kept per channel
+const MAX_CHANGES: usize = 50;
+/// How many streams starting are kept per channel
+const MAX_WENT_LIVE: usize = 10;
+
+/// Receives EventSub webhook notifications for tracked channels.
+///
+/// Everything learned from notifications only lives in memory.
+pub struct EventSub {
+    callback_url: String,
+    secret: String,
+    /// Serve tracked channels from `stored` instead of asking Helix on every request
+    zero_poll: bool,
+    /// Record `stream.online` in `went_live`
+    live_items: bool,
+    seen: Mutex<VecDeque<String>>,
+    changes: RwLock<HashMap<String, VecDeque<ChannelChange>>>,
+    went_live: RwLock<HashMap<String, VecDeque<WentLive>>>,
+    stored: RwLock<HashMap<String, StoredChannel>>,
+}
+
+/// What zero-poll mode knows about a tracked channel, refreshed when it goes on or offline.
+#[derive(Clone)]
+pub struct StoredChannel {
+    pub user: User,
+    pub videos: Vec<Video>,
+}
+
+/// A stream starting, reported by `stream.online`.
+#[derive(Clone, Debug)]
+pub struct WentLive {
+    pub stream_id: String,
+    pub started_at: OffsetDateTime,
+}
+
+/// A title or category change reported by `channel.update`.
+#[derive(Clone, Debug)]
+pub struct ChannelChange {
+    pub message_id: String,
+    pub title: String,
+    pub category_name: String,
+    pub at: OffsetDateTime,
+}
+
+impl EventSub {
+    pub fn new(callback_url: String, secret: String, zero_poll: bool, live_items: bool) -> Self {
+        Self {
+            callback_url,
+            secret,
+            zero_poll,
+            live_items,
+            seen: Mutex::new(VecDeque::new()),
+            changes: RwLock::new(HashMap::new()),
+            went_live: RwLock::new(HashMap::new()),
+            stored: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Drop what is stored for a channel that is no longer tracked.
+    pub async fn forget(&self, login: &str) {
+        self.stored.write().await.remove(login);
+    }
+
+    /// Recorded changes of `login`, newest first.
+    pub async fn changes(&self, login: &str) -> Vec<ChannelChange> {
+        self.changes
+            .read()
+            .await
+            .get(login)
+            .map(|c| c.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Recorded streams of `login` starting, newest first.
+    pub async fn went_live(&self, login: &str) -> Vec<WentLive> {
+        self.went_live
+            .read()
+            .await
+            .get(login)
+            .map(|w| w.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<Message, StatusCode> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .ok_or(StatusCode::BAD_REQUEST)
+        };
+
+        let id = header("twitch-eventsub-message-id")?;
+        let timestamp = header("twitch-eventsub-message-timestamp")?;
+        let signature = header("twitch-eventsub-message-signature")?;
+        let kind = header("twitch-eventsub-message-type")?;
+
+        let signature = signature
+            .strip_prefix("sha256=")
+            .and_then(|s| hex::decode(s).ok())
+            .ok_or(StatusCode::FORBIDDEN)?;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(id.as_bytes());
+        mac.update(timestamp.as_bytes());
+        mac.update(body);
+        mac.verify_slice(&signature)
+            .map_err(|_| StatusCode::FORBIDDEN)?;
+
+        let at = OffsetDateTime::parse(timestamp, &Rfc3339).map_err(|_| StatusCode::BAD_REQUEST)?;
+        if (OffsetDateTime::now_utc() - at).abs() > MAX_MESSAGE_AGE {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        Ok(Message {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            at,
+        })
+    }
+
+    /// Remember `id`, returning false if it was already delivered.
+    async fn first_delivery(&self, id: &str) -> bool {
+        let mut seen = self.seen.lock().await;
+        if seen.iter().any(|s| s == id) {
+            return false;
+        }
+        if seen.len() >= SEEN_MESSAGES {
+            seen.pop_front();
+        }
+        seen.push_back(id.to_string());
+        true
+    }
+
+    /// Record what `notification` says, returning what it made out of date.
+    async fn handle_notification(
+        &self,
+        message: &Message,
+        notification: Notification,
+    ) -> Option<Outdated> {
+        match notification.subscription.kind.as_str() {
+            "channel.update" => {
+                let Ok(event) = serde_json::from_value::<ChannelUpdate>(notification.event) else {
+                    println!("malformed channel.update notification {}", message.id);
+                    return None;
+                };
+
+                let mut changes = self.changes.write().await;
+                let channel = changes
+                    .entry(event.broadcaster_user_login.clone())
+                    .or_default();
+                if channel.len() >= MAX_CHANGES {
+                    channel.pop_front();
+                }
+                channel.push_back(ChannelChange {
+                    message_id: message.id.clone(),
+                    title: event.title,
+                    category_name: event.category_name,
+                    at: message.at,
+                });
+                Some(Outdated::Channel {
+                    login: event.broadcaster_user_login,
+                    user_id: event.broadcaster_user_id,
+                })
+            }
+            // a stream starting creates its archive, ending it finalizes the duration
+            kind @ ("stream.online" | "stream.offline") => {
+                match serde_json::from_value::<StreamEvent>(notification.event) {
+                    Ok(event) => {
+                        if kind == "stream.online" && self.live_items {
+                            self.record_went_live(&event, message.at).await;
+                        }
+                        Some(Outdated::Videos {
+                            login: event.broadcaster_user_login,
+                            user_id: event.broadcaster_user_id,
+                        })
+                    }
+                    Err(_) => {
+                        println!("malformed {} notification {}", kind, message.id);
+                        None
+                    }
+                }
+            }
+            other => {
+                println!("ignoring {} notification", other);
+                None
+            }
+        }
+    }
+
+    /// Remember `event` of a stream starting, which Twitch reported `at`.
+    async fn record_went_live(&self, event: &StreamEvent, at: OffsetDateTime) {
+        let Some(stream_id) = event.id.clone() else {
+            return;
+        };
+        let started_at = event
+            .started_at
+            .as_deref()
+            .and_then(|s| OffsetDateTime::parse(s, &Rfc3339).ok())
+            .unwrap_or(at);
+
+        let mut went_live = self.went_live.write().await;
+        let streams = went_live
+            .entry(event.broadcaster_user_login.clone())
+            .or_default();
+        // redelivered under a new message id, the stream is the same
+        if streams.iter().any(|w| w.stream_id == stream_id) {
+            return;
+        }
+        if streams.len() >= MAX_WENT_LIVE {
+            streams.pop_front();
+        }
+        streams.push_back(WentLive {
+            stream_id,
+            started_at,
+        });
+    }
+}
+
+/// What a notification made out of date, of the broadcaster it names. The id is always in the
+/// payload, unlike in the cache, so invalidation doesn't depend on a lookup still being cached.
+enum Outdated {
+    /// The channel information, like its title, category and tags
+    Channel { login: String, user_id: UserId },
+    /// The videos, a stream starting creates its archive and ending it finalizes the duration
+    Videos { login: String, user_id: UserId },
+}
+
+struct Message {
+    id: String,
+    kind: String,
+    at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+struct Verification {
+    challenge: String,
+}
+
+#[derive(Deserialize)]
+struct Notification {
+    subscription: Subscription,
+    event: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Subscription {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelUpdate {
+    broadcaster_user_id: UserId,
+    broadcaster_user_login: String,
+    title: String,
+    category_name: String,
+}
+
+#[derive(Deserialize)]
+struct StreamEvent {
+    broadcaster_user_id: UserId,
+    broadcaster_user_login: String,
+    /// Stream id, only in `stream.online`
+    id: Option<String>,
+    /// RFC 3339 timestamp, only in `stream.online`
+    started_at: Option<String>,
+}
+
+/// `POST /eventsub/callback`, the webhook Twitch delivers notifications to.
+pub async fn callback(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let Some(eventsub) = &state.eventsub else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let message = match eventsub.verify(&headers, &body) {
+        Ok(m) => m,
+        Err(status) => return status.into_response(),
+    };
+
+    match message.kind.as_str() {
+        "webhook_callback_verification" => match serde_json::from_slice::<Verification>(&body) {
+            Ok(v) => ([(header::CONTENT_TYPE, "text/plain")], v.challenge).into_response(),
+            Err(_) => StatusCode::BAD_REQUEST.into_response(),
+        },
+        "notification" => {
+            // twitch retries deliveries it considers failed, acknowledge duplicates without
+            // handling them again
+            if eventsub.first_delivery(&message.id).await {
+                match serde_json::from_slice::<Notification>(&body) {
+                    Ok(n) => match eventsub.handle_notification(&message, n).await {
+                        Some(Outdated::Channel { login, user_id }) => {
+                            state.cache.invalidate(&cache::channel_key(&login)).await;
+                            state
+                                .cache
+                                .invalidate(&cache::channel_tags_key(&user_id))
+                                .await;
+                            cache::invalidate_responses(&state.cache, &login, Some(&user_id)).await;
+                        }
+                        Some(Outdated::Videos { login, user_id }) => {
+                            tokio::spawn(videos_changed(state.clone(), login, user_id));
+                        }
+                        None => {}
+                    },
+                    Err(e) => println!("malformed notification {}: {}", message.id, e),
+                }
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        "revocation" => {
+            println!(
+                "eventsub subscription revoked: {}",
+                String::from_utf8_lossy(&body)
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        _ => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Create the EventSub subscriptions for `login`, if EventSub is enabled.
+///
+/// Subscriptions that already exist are left alone.
+pub async fn subscribe(state: &AppState, login: &str) -> Result<(), TwitchRssError> {
+    let Some(eventsub) = &state.eventsub else {
+        return Ok(());
+    };
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        login.into(),
+    )
+    .await?;
+
+    for (kind, version) in SUBSCRIPTIONS {
+        let body = serde_json::json!({
+            "type": kind,
+            "version": version,
+            "condition": { "broadcaster_user_id": user_id },
+            "transport": {
+                "method": "webhook",
+                "callback": eventsub.callback_url,
+                "secret": eventsub.secret,
+            },
+        });
+
+        let url = twitch_api2::TWITCH_HELIX_URL
+            .join("eventsub/subscriptions")
+            .expect("static path");
+
+        let response = state
+            .client
+            .post(url)
+            .header("Client-Id", token.client_id().as_str())
+            .bearer_auth(token.token().secret())
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| TwitchRssError::RequestError(format!("{}", e)))?;
+
+        match response.status() {
+            reqwest::StatusCode::ACCEPTED | reqwest::StatusCode::CONFLICT => {}
+            status => {
+                let text = response.text().await.unwrap_or_default();
+                return Err(TwitchRssError::RequestError(format!(
+                    "creating {} subscription for {} failed with {}: {}",
+                    kind, login, status, text
+                )));
+            }
+        }
+    }
+
+    println!("subscribed to eventsub for {}", login);
+
+    if eventsub.zero_poll {
+        backfill(state, login).await?;
+    }
+    Ok(())
+}
+
+/// Subscribe every tracked channel, used at startup.
+pub async fn subscribe_tracked(state: AppState) {
+    for login in state.tracked.list().await.into_keys() {
+        if let Err(e) = subscribe(&state, &login).await {
+            println!("{}", e);
+        }
+    }
+}
+
+/// Fetch the channel and its videos from Helix into the zero-poll store.
+async fn backfill(state: &AppState, login: &str) -> Result<(), TwitchRssError> {
+    let Some(eventsub) = &state.eventsub else {
+        return Ok(());
+    };
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user = get_user(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        login.into(),
+    )
+    .await?;
+
+    // a notification means the cached list is out of date
+    cache::invalidate_videos(&state.cache, &user.id).await;
+    let videos = get_user_videos(
+        &state.cache,
+        state.config.videos_ttl,
+        &helix_client,
+        &token,
+        user.id.clone(),
+        &VideoFilter::default(),
+    )
+    .await?;
+
+    println!("stored {} videos for {}", videos.len(), login);
+    eventsub
+        .stored
+        .write()
+        .await
+        .insert(login.to_string(), StoredChannel { user, videos });
+    Ok(())
+}
+
+async fn backfill_logged(state: AppState, login: String) {
+    if let Err(e) = backfill(&state, &login).await {
+        println!("refreshing {} failed: {}", login, e);
+    }
+}
+
+/// Catch up with the videos of `login` after a notification: drop the cached ones so the next
+/// request sees the new video within seconds, refresh the zero-poll store, then ping the WebSub hub.
+async fn videos_changed(state: AppState, login: String, user_id: UserId) {
+    cache::invalidate_videos(&state.cache, &user_id).await;
+    cache::invalidate_responses(&state.cache, &login, Some(&user_id)).await;
+    if state.eventsub.as_ref().is_some_and(|e| e.zero_poll) {
+        backfill_logged(state.clone(), login.clone()).await;
+    }
+    websub::publish(&state, &login).await;
+}
+
+/// In zero-poll mode, what is stored for `login`.
+///
+/// Untracked channels are refused rather than fetched so reader traffic never reaches Helix.
+/// `None` means the request should go to Helix as usual, either because the mode is off or
+/// because a tracked channel hasn't been backfilled yet.
+pub async fn stored_channel(
+    state: &AppState,
+    login: &str,
+) -> Result<Option<StoredChannel>, TwitchRssError> {
+    let Some(eventsub) = state.eventsub.as_ref().filter(|e| e.zero_poll) else {
+        return Ok(None);
+    };
+
+    if let Some(stored) = eventsub.stored.read().await.get(login) {
+        return Ok(Some(stored.clone()));
+    }
+    if state.tracked.get(login).await.is_none() {
+        return Err(TwitchRssError::NotTracked(login.to_string()));
+    }
+    Ok(None)
+}
+
+/// With `EVENTSUB_LIVE_ITEMS`, add an item for every recorded stream of `login` starting to
+/// `items`, each before the first item older than it.
+///
+/// Items are keyed by stream id, like the item pinned for a running stream, so a broadcast is one
+/// item however often it is seen.
+pub async fn add_went_live(
+    state: &AppState,
+    login: &str,
+    items: &mut Vec<FeedItem>,
+    locale: Locale,
+    time_zone: &TimeZone,
+) {
+    let Some(eventsub) = state.eventsub.as_ref().filter(|e| e.live_items) else {
+        return;
+    };
+
+    let link = format!("https://www.twitch.tv/{}", login);
+    for went_live in eventsub.went_live(login).await {
+        let started = time_zone.format(went_live.started_at);
+        let item = FeedItem {
+            id: format!("live-{}", went_live.stream_id),
+            title: locale.went_live(&started),
+            link: link.clone(),
+            description: format!("<a href=\"{}\">{}</a>", link, locale.went_live(&started)),
+            published: went_live.started_at,
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: Some(login.to_string()),
+        };
+
+        let at = items
+            .iter()
+            .position(|i| i.published < item.published)
+            .unwrap_or(items.len());
+        items.insert(at, item);
+    }
+}
+
+/// `/channel/{name}/changes`, a feed of title and category changes.
+pub async fn changes(
+    Path(name): Path<String>,
+    Query(query): Query<LocaleQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Response, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+
+    let Some(eventsub) = &state.eventsub else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    // only tracked channels have subscriptions, an empty feed would look like a quiet channel
+    if state.tracked.get(&name).await.is_none() {
+        return Err(TwitchRssError::NotTracked(name));
+    }
+
+    let link = format!("https://www.twitch.tv/{}", name);
+    let items = eventsub
+        .changes(&name)
+        .await
+        .into_iter()
+        .map(|c| FeedItem {
+            id: c.message_id,
+            title: format!("{}: {}", c.category_name, c.title),
+            link: link.clone(),
+            description: format!(
+                "{}: {}<br />{}: {}",
+                locale.title(),
+                c.title,
+                locale.category(),
+                c.category_name
+            ),
+            published: c.at,
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: Some(c.category_name),
+            enclosure: None,
+            tags: Vec::new(),
+            channel: Some(name.clone()),
+        })
+        .collect();
+
+    let mut feed = Feed {
+        title: locale.changes_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}/about", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: None,
+        items,
+    };
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming::twitch(&name, "channel changes"),
+    );
+
+    Ok(render_feed(&state, &uri, feed).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    fn eventsub() -> EventSub {
+        EventSub::new(
+            "https://example.com/eventsub".to_string(),
+            SECRET.to_string(),
+            false,
+            false,
+        )
+    }
+
+    fn headers(id: &str, at: OffsetDateTime, body: &[u8]) -> HeaderMap {
+        let timestamp = at.format(&Rfc3339).unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(id.as_bytes());
+        mac.update(timestamp.as_bytes());
+        mac.update(body);
+        let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("twitch-eventsub-message-id", id.parse().unwrap());
+        headers.insert(
+            "twitch-eventsub-message-timestamp",
+            timestamp.parse().unwrap(),
+        );
+        headers.insert(
+            "twitch-eventsub-message-signature",
+            signature.parse().unwrap(),
+        );
+        headers.insert(
+            "twitch-eventsub-message-type",
+            "notification".parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let body = br#"{"subscription":{}}"#;
+        let message = eventsub()
+            .verify(&headers("abc", OffsetDateTime::now_utc(), body), body)
+            .unwrap();
+        assert_eq!(message.id, "abc");
+        assert_eq!(message.kind, "notification");
+    }
+
+    #[test]
+    fn rejects_a_tampered_body() {
+        let headers = headers("abc", OffsetDateTime::now_utc(), br#"{"a":1}"#);
+        let result = eventsub().verify(&headers, br#"{"a":2}"#);
+        assert_eq!(result.err(), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn rejects_stale_and_future_timestamps() {
+        let body = b"{}";
+        let now = OffsetDateTime::now_utc();
+        let eventsub = eventsub();
+        for at in [
+            now - MAX_MESSAGE_AGE - time::Duration::minutes(1),
+            now + MAX_MESSAGE_AGE + time::Duration::minutes(1),
+        ] {
+            let result = eventsub.verify(&headers("abc", at, body), body);
+            assert_eq!(result.err(), Some(StatusCode::FORBIDDEN), "{at}");
+        }
+        let slightly_ahead = now + time::Duration::seconds(30);
+        assert!(eventsub
+            .verify(&headers("abc", slightly_ahead, body), body)
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_missing_headers() {
+        let result = eventsub().verify(&HeaderMap::new(), b"{}");
+        assert_eq!(result.err(), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn drops_a_repeated_message_id() {
+        let eventsub = eventsub();
+        assert!(eventsub.first_delivery("abc").await);
+        assert!(eventsub.first_delivery("def").await);
+        assert!(!eventsub.first_delivery("abc").await);
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use axum::extract::{OriginalUri, Path, Query, State};
+use time::OffsetDateTime;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::types::{BroadcasterType, VideoType};
+
+use crate::feed::{Feed, FeedItem, FeedNaming};
+use crate::i18n::Locale;
+use crate::options::{FeedOptions, FeedQuery};
+use crate::render::Rendered;
+use crate::{login, provider, render_feed, AppState, TwitchRssError};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Archives in the last 1/WARNING_SHARE of their retention are annotated and listed in the
+/// expiring feed, a fixed window would cover the whole lifetime of short retentions
+const WARNING_SHARE: u32 = 4;
+
+/// How long Twitch keeps past broadcasts for a broadcaster type.
+///
+/// Turbo and Prime users get the partner window too, but that isn't visible through the API, so
+/// those channels may keep their archives longer than this says.
+pub fn retention(broadcaster_type: Option<&BroadcasterType>) -> Duration {
+    match broadcaster_type {
+        Some(BroadcasterType::Partner) => Duration::from_secs(60 * DAY),
+        Some(BroadcasterType::Affiliate) => Duration::from_secs(14 * DAY),
+        _ => Duration::from_secs(7 * DAY),
+    }
+}
+
+/// When `video` will be deleted, only past broadcasts expire.
+pub fn expires_at(video: &Video, retention: Duration) -> Option<OffsetDateTime> {
+    (video.type_ == VideoType::Archive).then(|| video.created_at.to_utc() + retention)
+}
+
+fn expiring_soon(video: &Video, retention: Duration, now: OffsetDateTime) -> Option<Duration> {
+    let left = expires_at(video, retention)? - now;
+    let left = Duration::try_from(left).ok()?;
+    (left <= retention / WARNING_SHARE).then_some(left)
+}
+
+/// Note in the description of items whose video is close to being deleted.
+///
+/// `items` and `videos` have to be in the same order, as returned by
+/// [`provider::twitch_items`].
+pub fn annotate(
+    items: Vec<FeedItem>,
+    videos: &[Video],
+    retention: Duration,
+    locale: Locale,
+) -> Vec<FeedItem> {
+    let now = OffsetDateTime::now_utc();
+
+    items
+        .into_iter()
+        .zip(videos)
+        .map(|(item, video)| match expiring_soon(video, retention, now) {
+            Some(left) => FeedItem {
+                description: format!(
+                    "<b>{}</b><br />{}",
+                    locale.expires_in(left.as_secs().div_ceil(DAY)),
+                    item.description
+                ),
+                ..item
+            },
+            None => item,
+        })
+        .collect()
+}
+
+/// `/channel/{name}/vod/expiring`, only the videos that are about to be deleted.
+pub async fn expiring(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let (videos, retention) = tokio::try_join!(
+        provider::twitch_videos(&state, &name, &options.videos),
+        provider::twitch_retention(&state, &name)
+    )?;
+
+    let now = OffsetDateTime::now_utc();
+    let videos = videos
+        .into_iter()
+        .filter(|v| expiring_soon(v, retention, now).is_some())
+        .collect::<Vec<_>>();
+
+    let locale = options.items.locale.unwrap_or(state.config.locale);
+    let items = annotate(
+        provider::twitch_items(&state, &name, &videos, &options.items),
+        &videos,
+        retention,
+        locale,
+    );
+
+    let mut feed = Feed {
+        title: locale.expiring_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}/videos", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(provider::videos_ttl(&state, &name).await),
+        items: provider::finish_items(&state, &options, items).await,
+    };
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming::twitch(&name, "VODs expiring soon"),
+    );
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::format_description::well_known::Rfc3339;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn archive(created_at: &str) -> Video {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "stream_id": null,
+            "user_id": "123",
+            "user_login": "foo",
+            "user_name": "Foo",
+            "title": "Some stream",
+            "description": "",
+            "created_at": created_at,
+            "published_at": created_at,
+            "url": "https://www.twitch.tv/videos/1",
+            "thumbnail_url": "https://x/%{width}x%{height}.jpg",
+            "viewable": "public",
+            "view_count": 0,
+            "language": "en",
+            "type": "archive",
+            "duration": "1h",
+            "muted_segments": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn warns_in_the_last_quarter_of_the_retention() {
+        let video = archive("2024-06-01T00:00:00Z");
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+
+        // non-affiliates keep archives for 7 days, so the warning starts after 5 days 6 hours
+        let retention = retention(None);
+        let now = datetime!(2024-06-06 05:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), None);
+        let now = datetime!(2024-06-06 06:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), Some(hours(42)));
+        let now = datetime!(2024-06-08 00:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), Some(Duration::ZERO));
+        let now = datetime!(2024-06-08 00:01 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), None);
+
+        // affiliates keep them for 14, so a week old one isn't expiring yet
+        let retention = super::retention(Some(&BroadcasterType::Affiliate));
+        let now = datetime!(2024-06-08 00:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), None);
+        let now = datetime!(2024-06-11 11:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), None);
+        let now = datetime!(2024-06-11 12:00 UTC);
+        assert_eq!(expiring_soon(&video, retention, now), Some(hours(84)));
+    }
+
+    #[test]
+    fn only_archives_expire() {
+        let mut video = archive("2024-06-01T00:00:00Z");
+        video.type_ = VideoType::Highlight;
+        let now = datetime!(2024-06-07 00:00 UTC);
+        assert_eq!(expiring_soon(&video, retention(None), now), None);
+    }
+
+    #[test]
+    fn notes_the_days_left_in_the_locale() {
+        let created_at = OffsetDateTime::now_utc() - time::Duration::days(6);
+        let video = archive(&created_at.format(&Rfc3339).unwrap());
+        let item = FeedItem {
+            id: "1".to_string(),
+            title: "Some stream".to_string(),
+            link: "https://www.twitch.tv/videos/1".to_string(),
+            description: "Some stream".to_string(),
+            published: created_at,
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: None,
+        };
+        let items = annotate(vec![item], &[video], retention(None), Locale::De);
+        assert_eq!(
+            items[0].description,
+            "<b>Läuft in weniger als einem Tag ab</b><br />Some stream"
+        );
+    }
+}
@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+use twitch_api2::helix::clips::Clip;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::types::VideoPrivacy;
+
+use crate::config::ChannelMetadata;
+use crate::duration;
+use crate::i18n::Locale;
+use crate::timezone::TimeZone;
+
+/// Output format independent description of a feed, turned into bytes by a
+/// [`FeedRenderer`](crate::render::FeedRenderer).
+#[derive(Clone, Debug)]
+pub struct Feed {
+    pub title: String,
+    /// Page the feed follows, used as the RSS channel link and the Atom feed id
+    pub link: String,
+    /// Picture of the channel the feed follows, for formats that show one
+    pub image: Option<String>,
+    /// Plain text, e.g. the broadcaster's bio
+    pub description: Option<String>,
+    /// Language code like `en`
+    pub language: Option<String>,
+    /// Public URL of the feed itself, filled in when it is rendered
+    pub self_link: Option<String>,
+    /// WebSub hub to advertise, only set where the hub is told about changes of the feed
+    pub hub: Option<String>,
+    /// How long the content is cached, so readers needn't poll more often
+    pub ttl: Option<std::time::Duration>,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FeedItem {
+    /// Stable identifier, used as the RSS guid
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    /// HTML description
+    pub description: String,
+    pub published: OffsetDateTime,
+    /// Only subscribers of the channel can watch it
+    pub sub_only: bool,
+    /// Length in seconds, for recordings
+    pub duration: Option<u64>,
+    /// URL of a preview image
+    pub thumbnail: Option<String>,
+    /// View count, for videos and clips
+    pub views: Option<u64>,
+    /// Game or category it was streamed under, when known
+    pub category: Option<String>,
+    /// Playable media for podcast and download clients
+    pub enclosure: Option<Enclosure>,
+    /// Tags of the channel, written as categories next to `category`
+    pub tags: Vec<String>,
+    /// Login of the channel it is from, when known
+    pub channel: Option<String>,
+}
+
+/// Placeholders `FEED_TITLE` and `FEED_DESCRIPTION` can contain.
+pub const FEED_PLACEHOLDERS: [&str; 5] = ["login", "name", "platform", "feed", "description"];
+
+/// The channel and kind of a feed, filled into the title and description templates.
+pub struct FeedNaming<'a> {
+    pub login: &'a str,
+    /// Display name, the login for feeds that don't fetch the user
+    pub display_name: &'a str,
+    pub platform: &'a str,
+    /// What the feed lists, like `VODs` or `clips`
+    pub kind: &'a str,
+}
+
+impl<'a> FeedNaming<'a> {
+    /// The `kind` feed of Twitch channel `login`, named by login.
+    pub fn twitch(login: &'a str, kind: &'a str) -> Self {
+        Self {
+            login,
+            display_name: login,
+            platform: "Twitch",
+            kind,
+        }
+    }
+}
+
+impl Feed {
+    /// Give the feed the configured title and description, where templates for them are set.
+    ///
+    /// `{description}` is the description the feed already has, e.g. the broadcaster's bio.
+    pub fn apply_templates(&mut self, metadata: &ChannelMetadata, naming: &FeedNaming) {
+        let current = self.description.take().unwrap_or_default();
+        let values = [
+            ("login", naming.login),
+            ("name", naming.display_name),
+            ("platform", naming.platform),
+            ("feed", naming.kind),
+            ("description", current.as_str()),
+        ];
+        // templates are validated on startup, so placeholders are always known
+        let fill = |template: &str| fill_template(template, &values).unwrap_or_default();
+
+        if let Some(template) = &metadata.title_template {
+            self.title = fill(template);
+        }
+        self.description = match &metadata.description_template {
+            // around an empty `{description}` there may be nothing but separators left
+            Some(template) => Some(fill(template).trim().to_string()),
+            None => Some(current),
+        }
+        .filter(|d| !d.is_empty());
+    }
+}
+
+/// Replace every `{name}` in `template` with its value, in a single pass so values are never
+/// expanded themselves.
+///
+/// Fails with the name of the first placeholder that has no value.
+pub fn fill_template(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        filled.push_str(&rest[..start]);
+        let name = &rest[start + 1..start + len];
+        match values.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => filled.push_str(value),
+            None => return Err(name.to_string()),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    filled.push_str(rest);
+
+    Ok(filled)
+}
+
+impl FeedItem {
+    /// The category followed by the channel's tags, as written to feeds.
+    pub fn categories(&self) -> impl Iterator<Item = &String> {
+        self.category.iter().chain(&self.tags)
+    }
+}
+
+/// A media file an item can be played or downloaded as.
+#[derive(Clone, Debug)]
+pub struct Enclosure {
+    pub url: String,
+    pub mime_type: String,
+}
+
+/// Order items newest first, whatever order they were fetched or merged in.
+///
+/// Items published at the same moment are ordered by id so the output is stable between requests.
+pub fn sort_items(items: &mut [FeedItem]) {
+    items.sort_by(|a, b| b.published.cmp(&a.published).then_with(|| a.id.cmp(&b.id)));
+}
+
+/// Order items most viewed first, then newest first.
+pub fn sort_items_by_views(items: &mut [FeedItem]) {
+    sort_items(items);
+    items.sort_by_key(|i| std::cmp::Reverse(i.views));
+}
+
+/// How long before the newest airing of a title earlier airings are taken for the same broadcast.
+const RERUN_WINDOW: Duration = Duration::days(14);
+
+/// Words streamers mark reruns with, ignored when comparing titles along with the sub-only labels
+/// of [`video_to_item`] since a rerun may be free to watch.
+const RERUN_MARKERS: [&str; 4] = ["rerun", "re-run", "restream", "rebroadcast"];
+
+/// Airings of one title within [`RERUN_WINDOW`] of the newest, newest first.
+struct Airings {
+    items: Vec<usize>,
+    /// Whether any of them is marked as a rerun
+    rerun: bool,
+}
+
+/// Collapse reruns into the newest airing of the broadcast they repeat, keeping the order of
+/// `items`.
+///
+/// Items titled alike, give or take rerun markers and punctuation, within [`RERUN_WINDOW`] of the
+/// newest of them are airings of one broadcast, if at least one of them is marked as a rerun. A
+/// channel that always uses the same title is left alone that way. The newest airing is kept and
+/// mentions the others.
+pub fn collapse_reruns(items: &mut Vec<FeedItem>, time_zone: &TimeZone, locale: Locale) {
+    let mut by_time = (0..items.len()).collect::<Vec<_>>();
+    by_time.sort_by_key(|&i| std::cmp::Reverse(items[i].published));
+
+    // by title, newest series first
+    let mut series = HashMap::<String, Vec<Airings>>::new();
+    for i in by_time {
+        let title = rerun_title(&items[i].title);
+        if title.is_empty() {
+            continue;
+        }
+
+        let rerun = is_rerun(&items[i].title);
+        let airings = series.entry(title).or_default();
+        match airings.last_mut() {
+            Some(last) if items[last.items[0]].published - items[i].published <= RERUN_WINDOW => {
+                last.items.push(i);
+                last.rerun |= rerun;
+            }
+            _ => airings.push(Airings {
+                items: vec![i],
+                rerun,
+            }),
+        }
+    }
+
+    let mut keep = vec![true; items.len()];
+    for airings in series.into_values().flatten() {
+        let [newest, earlier @ ..] = airings.items.as_slice() else {
+            continue;
+        };
+        let Some(&first) = earlier.last().filter(|_| airings.rerun) else {
+            continue;
+        };
+
+        for &i in earlier {
+            keep[i] = false;
+        }
+        let first = time_zone.local(items[first].published).date().to_string();
+        items[*newest]
+            .description
+            .push_str(&format!("<br />{}", locale.reruns(earlier.len(), &first)));
+    }
+
+    let mut keep = keep.into_iter();
+    items.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Whether `title` has one of the [`RERUN_MARKERS`].
+fn is_rerun(title: &str) -> bool {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .any(|word| RERUN_MARKERS.contains(&word))
+}
+
+/// `title` lowercased without rerun markers and punctuation.
+fn rerun_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|word| {
+            // a dash on its own, like in `Title - Rerun`, is punctuation too
+            word.chars().any(char::is_alphanumeric)
+                && !RERUN_MARKERS.contains(word)
+                && !Locale::ALL
+                    .iter()
+                    .any(|l| l.sub_only_label().to_lowercase() == *word)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Which timestamp of a video items are dated with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PubDate {
+    /// When the stream started, or the video was uploaded
+    #[default]
+    Created,
+    /// When the video was made public, which is much later for highlights of old streams
+    Published,
+}
+
+impl FromStr for PubDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created_at" => Ok(Self::Created),
+            "published_at" => Ok(Self::Published),
+            _ => Err(format!("{:?} is not created_at or published_at", s)),
+        }
+    }
+}
+
+impl PubDate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created_at",
+            Self::Published => "published_at",
+        }
+    }
+}
+
+/// How videos are presented as feed items.
+#[derive(Clone, Copy, Debug)]
+pub struct ItemStyle<'a> {
+    /// Size of the thumbnail readers show by default, and of the player
+    pub size: ThumbnailSize,
+    /// Leave thumbnails out, both of the description and the item
+    pub no_thumbnail: bool,
+    /// Offer more thumbnail sizes for readers to pick from for the screen they are on
+    pub srcset: bool,
+    pub date: PubDate,
+    /// Sites the embedded player may be shown on, Twitch's `parent`. The player takes the place
+    /// of the thumbnail in the description, no player is embedded if empty.
+    pub embed_parents: &'a [String],
+    /// Language of the generated parts of titles and descriptions
+    pub locale: Locale,
+}
+
+/// Sizes offered in the `srcset` of description images.
+const SRCSET_SIZES: [ThumbnailSize; 4] = [
+    ThumbnailSize {
+        width: 320,
+        height: 180,
+    },
+    ThumbnailSize {
+        width: 640,
+        height: 360,
+    },
+    ThumbnailSize {
+        width: 1280,
+        height: 720,
+    },
+    ThumbnailSize {
+        width: 1920,
+        height: 1080,
+    },
+];
+
+/// Size thumbnails are requested in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ThumbnailSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ThumbnailSize {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 288,
+        }
+    }
+}
+
+impl FromStr for ThumbnailSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a size like 512x288", s);
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        let size = Self {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+        };
+        if size.width == 0 || size.height == 0 {
+            return Err(invalid());
+        }
+        Ok(size)
+    }
+}
+
+impl TryFrom<String> for ThumbnailSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+pub fn video_to_item(input: &Video, style: &ItemStyle) -> FeedItem {
+    let sub_only = is_sub_only(input);
+
+    let (title, description) = if sub_only {
+        (
+            format!("[{}] {}", style.locale.sub_only_label(), input.title),
+            format!(
+                "<b>{}</b><br />{}",
+                style.locale.sub_only_notice(),
+                build_description(input, style)
+            ),
+        )
+    } else {
+        (input.title.clone(), build_description(input, style))
+    };
+
+    FeedItem {
+        id: input.id.to_string(),
+        title,
+        link: input.url.clone(),
+        description,
+        published: match style.date {
+            PubDate::Created => input.created_at.to_utc(),
+            PubDate::Published => input.published_at.to_utc(),
+        },
+        sub_only,
+        duration: duration::parse(&input.duration),
+        thumbnail: (!style.no_thumbnail).then(|| thumbnail_url(input, style.size)),
+        views: u64::try_from(input.view_count).ok(),
+        category: None,
+        enclosure: None,
+        tags: Vec::new(),
+        channel: Some(input.user_login.to_string()),
+    }
+}
+
+/// Helix doesn't flag subscriber-only videos directly, `viewable` is the only hint it gives.
+pub fn is_sub_only(input: &Video) -> bool {
+    input.viewable == VideoPrivacy::Private
+}
+
+/// Videos still being processed, typically right after a stream ends, have a placeholder
+/// thumbnail and their page 404s.
+pub fn is_processing(input: &Video) -> bool {
+    input.thumbnail_url.is_empty() || input.thumbnail_url.contains("/_404/")
+}
+
+pub fn thumbnail_url(input: &Video, size: ThumbnailSize) -> String {
+    input
+        .thumbnail_url
+        .replace("%{width}", &size.width.to_string())
+        .replace("%{height}", &size.height.to_string())
+}
+
+fn build_description(input: &Video, style: &ItemStyle) -> String {
+    let mut lines = Vec::new();
+    let size = style.size;
+
+    if !style.embed_parents.is_empty() {
+        let parents = style
+            .embed_parents
+            .iter()
+            .map(|p| format!("&amp;parent={}", p))
+            .collect::<String>();
+        lines.push(format!(
+            "<iframe src=\"https://player.twitch.tv/?video={}{}&amp;autoplay=false\" \
+             width=\"{}\" height=\"{}\" allowfullscreen></iframe>",
+            input.id, parents, size.width, size.height
+        ));
+    } else if !style.no_thumbnail {
+        let srcset = match style.srcset {
+            true => format!(
+                " srcset=\"{}\" sizes=\"(max-width: {}px) 100vw, {}px\"",
+                SRCSET_SIZES
+                    .iter()
+                    .map(|s| format!("{} {}w", thumbnail_url(input, *s), s.width))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                size.width,
+                size.width
+            ),
+            false => String::new(),
+        };
+        lines.push(format!(
+            "<a href=\"{}\"><img src=\"{}\"{} /></a>",
+            input.url,
+            thumbnail_url(input, size),
+            srcset
+        ));
+    }
+
+    let mut metadata = Vec::new();
+    if let Some(secs) = duration::parse(&input.duration) {
+        metadata.push(style.locale.duration(secs));
+    }
+    metadata.push(style.locale.views(input.view_count));
+    lines.push(format!("<small>{}</small>", metadata.join(" · ")));
+
+    // include twitch video description if it exists
+    if !input.description.is_empty() {
+        lines.push(input.description.clone());
+    }
+
+    // include video title for buggy RSS readers that only update if the description itself changes
+    lines.push(input.title.clone());
+    lines.join("<br />")
+}
+
+pub fn clip_to_item(input: &Clip, locale: Locale) -> FeedItem {
+    let description = format!(
+        "<a href=\"{}\"><img src=\"{}\" /></a><br />{}<br />{}",
+        input.url,
+        input.thumbnail_url,
+        locale.clipped_by(input.creator_name.as_str(), input.view_count),
+        input.title
+    );
+
+    FeedItem {
+        id: input.id.clone(),
+        title: input.title.clone(),
+        link: input.url.clone(),
+        description,
+        published: input.created_at.to_utc(),
+        sub_only: false,
+        duration: Some(input.duration.round() as u64),
+        thumbnail: Some(input.thumbnail_url.clone()),
+        views: u64::try_from(input.view_count).ok(),
+        category: None,
+        enclosure: None,
+        tags: Vec::new(),
+        channel: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn item(id: &str, published: OffsetDateTime) -> FeedItem {
+        FeedItem {
+            id: id.to_string(),
+            title: String::new(),
+            link: String::new(),
+            description: String::new(),
+            published,
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: None,
+        }
+    }
+
+    fn video(duration: &str, view_count: i64, description: &str) -> Video {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "stream_id": null,
+            "user_id": "123",
+            "user_login": "foo",
+            "user_name": "Foo",
+            "title": "Some stream",
+            "description": description,
+            "created_at": "2024-06-01T00:00:00Z",
+            "published_at": "2024-06-01T00:00:00Z",
+            "url": "https://www.twitch.tv/videos/1",
+            "thumbnail_url": "https://x/%{width}x%{height}.jpg",
+            "viewable": "public",
+            "view_count": view_count,
+            "language": "en",
+            "type": "archive",
+            "duration": duration,
+            "muted_segments": null,
+        }))
+        .unwrap()
+    }
+
+    fn style(srcset: bool) -> ItemStyle<'static> {
+        ItemStyle {
+            size: ThumbnailSize::default(),
+            no_thumbnail: false,
+            srcset,
+            date: PubDate::Created,
+            embed_parents: &[],
+            locale: Locale::En,
+        }
+    }
+
+    fn ids(items: &[FeedItem]) -> Vec<&str> {
+        items.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    #[test]
+    fn sorts_newest_first() {
+        let mut items = vec![
+            item("old", datetime!(2024-01-01 0:00 UTC)),
+            item("new", datetime!(2024-03-01 0:00 UTC)),
+            item("mid", datetime!(2024-02-01 0:00 UTC)),
+        ];
+        sort_items(&mut items);
+        assert_eq!(ids(&items), ["new", "mid", "old"]);
+    }
+
+    #[test]
+    fn compares_instants_across_offsets() {
+        let mut items = vec![
+            item("earlier", datetime!(2024-01-01 12:00 +02:00)),
+            item("later", datetime!(2024-01-01 11:00 UTC)),
+        ];
+        sort_items(&mut items);
+        assert_eq!(ids(&items), ["later", "earlier"]);
+    }
+
+    #[test]
+    fn describes_duration_and_views_under_the_thumbnail() {
+        let description = build_description(&video("3h12m5s", 1234, ""), &style(false));
+        assert_eq!(
+            description,
+            "<a href=\"https://www.twitch.tv/videos/1\"><img src=\"https://x/512x288.jpg\" /></a>\
+             <br /><small>3h 12m · 1,234 views</small><br />Some stream"
+        );
+    }
+
+    #[test]
+    fn keeps_the_video_description_after_the_metadata() {
+        let description = build_description(&video("45m", 1, "Hello"), &style(false));
+        assert!(
+            description.ends_with("<br /><small>45m · 1 view</small><br />Hello<br />Some stream")
+        );
+    }
+
+    #[test]
+    fn leaves_out_unknown_durations() {
+        let description = build_description(&video("", 0, ""), &style(false));
+        assert!(description.contains("<br /><small>0 views</small>"));
+    }
+
+    #[test]
+    fn offers_more_thumbnail_sizes() {
+        let description = build_description(&video("45m", 2, ""), &style(true));
+        assert!(description.starts_with(
+            "<a href=\"https://www.twitch.tv/videos/1\"><img src=\"https://x/512x288.jpg\" \
+             srcset=\"https://x/320x180.jpg 320w, https://x/640x360.jpg 640w, \
+             https://x/1280x720.jpg 1280w, https://x/1920x1080.jpg 1920w\" \
+             sizes=\"(max-width: 512px) 100vw, 512px\" /></a><br />"
+        ));
+    }
+
+    #[test]
+    fn embeds_the_player_instead_of_the_thumbnail() {
+        let parents = ["example.com".to_string(), "reader.example".to_string()];
+        let description = build_description(
+            &video("45m", 2, ""),
+            &ItemStyle {
+                embed_parents: &parents,
+                ..style(true)
+            },
+        );
+        assert_eq!(
+            description,
+            "<iframe src=\"https://player.twitch.tv/?video=1&amp;parent=example.com\
+             &amp;parent=reader.example&amp;autoplay=false\" width=\"512\" height=\"288\" \
+             allowfullscreen></iframe><br /><small>45m · 2 views</small><br />Some stream"
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_thumbnail() {
+        let description = build_description(
+            &video("45m", 2, ""),
+            &ItemStyle {
+                no_thumbnail: true,
+                ..style(false)
+            },
+        );
+        assert_eq!(description, "<small>45m · 2 views</small><br />Some stream");
+    }
+
+    #[test]
+    fn breaks_ties_by_id() {
+        let at = datetime!(2024-01-01 0:00 UTC);
+        let mut items = vec![item("b", at), item("c", at), item("a", at)];
+        sort_items(&mut items);
+        assert_eq!(ids(&items), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn fills_placeholders_once() {
+        let values = [("name", "Foo"), ("description", "{name}")];
+        assert_eq!(
+            fill_template("{name} VODs: {description}", &values),
+            Ok("Foo VODs: {name}".to_string())
+        );
+        assert_eq!(fill_template("a {b", &values), Ok("a {b".to_string()));
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert_eq!(
+            fill_template("{name} {nope}", &[("name", "Foo")]),
+            Err("nope".to_string())
+        );
+    }
+
+    fn titled(id: &str, title: &str, published: OffsetDateTime) -> FeedItem {
+        FeedItem {
+            title: title.to_string(),
+            ..item(id, published)
+        }
+    }
+
+    #[test]
+    fn collapses_consecutive_reruns() {
+        let mut items = vec![
+            titled("3", "Any% attempts (Rerun)", datetime!(2024-06-10 0:00 UTC)),
+            titled(
+                "2",
+                "[Sub-only] Any% attempts",
+                datetime!(2024-06-05 0:00 UTC),
+            ),
+            titled("1", "Any% attempts!", datetime!(2024-06-01 0:00 UTC)),
+        ];
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        assert_eq!(ids(&items), ["3"]);
+        assert_eq!(
+            items[0].description,
+            "<br />Aired 2 times before, first on 2024-06-01"
+        );
+    }
+
+    #[test]
+    fn collapses_reruns_between_other_broadcasts() {
+        let mut items = vec![
+            titled("4", "Charity stream RERUN", datetime!(2024-06-04 0:00 UTC)),
+            titled("3", "Any% attempts - rerun", datetime!(2024-06-03 0:00 UTC)),
+            titled("2", "Charity stream", datetime!(2024-06-02 0:00 UTC)),
+            titled("1", "Any% attempts", datetime!(2024-06-01 0:00 UTC)),
+        ];
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        assert_eq!(ids(&items), ["4", "3"]);
+        assert_eq!(
+            items[0].description,
+            "<br />Aired once before, on 2024-06-02"
+        );
+        assert_eq!(
+            items[1].description,
+            "<br />Aired once before, on 2024-06-01"
+        );
+    }
+
+    #[test]
+    fn keeps_repeats_outside_the_rerun_window() {
+        let mut items = vec![
+            titled("2", "Weekly race", datetime!(2024-06-20 0:00 UTC)),
+            titled("1", "Weekly race", datetime!(2024-06-01 0:00 UTC)),
+        ];
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        assert_eq!(ids(&items), ["2", "1"]);
+        assert!(items.iter().all(|i| i.description.is_empty()));
+    }
+
+    #[test]
+    fn keeps_a_channel_that_always_uses_the_same_title() {
+        let mut items = (1..=5)
+            .rev()
+            .map(|day| {
+                titled(
+                    &day.to_string(),
+                    "Chill stream",
+                    datetime!(2024-06-01 0:00 UTC) + Duration::days(day),
+                )
+            })
+            .collect::<Vec<_>>();
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        assert_eq!(ids(&items), ["5", "4", "3", "2", "1"]);
+        assert!(items.iter().all(|i| i.description.is_empty()));
+    }
+
+    #[test]
+    fn starts_a_new_series_outside_the_window() {
+        let mut items = vec![
+            titled("3", "Any% attempts (Rerun)", datetime!(2024-06-30 0:00 UTC)),
+            titled("2", "Any% attempts (Rerun)", datetime!(2024-06-05 0:00 UTC)),
+            titled("1", "Any% attempts", datetime!(2024-06-01 0:00 UTC)),
+        ];
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        // the newest airing is too late to join the others, which keep their note
+        assert_eq!(ids(&items), ["3", "2"]);
+        assert_eq!(items[0].description, "");
+        assert_eq!(
+            items[1].description,
+            "<br />Aired once before, on 2024-06-01"
+        );
+    }
+
+    #[test]
+    fn leaves_a_single_item_alone() {
+        let mut items = vec![titled("1", "Any% attempts", datetime!(2024-06-01 0:00 UTC))];
+        collapse_reruns(&mut items, &TimeZone::utc(), Locale::En);
+
+        assert_eq!(ids(&items), ["1"]);
+        assert_eq!(items[0].description, "");
+    }
+}
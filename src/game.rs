@@ -0,0 +1,235 @@
+use axum::extract::{OriginalUri, Path, Query, State};
+use reqwest::Url;
+use twitch_api2::helix::games::Game;
+use twitch_api2::helix::HelixClient;
+
+use crate::clips::{self, ClipWindow, WindowQuery};
+use crate::feed::{Feed, FeedItem};
+use crate::options::{self, FeedOptions, FeedQuery, LocaleQuery};
+use crate::render::Rendered;
+use crate::{category, live, provider, render_feed};
+use crate::{get_game, get_game_clips, get_game_streams, get_game_videos, get_token};
+use crate::{AppState, TwitchRssError};
+
+/// Longer than any category Twitch has, short enough to not be worth asking about
+const MAX_GAME_LEN: usize = 100;
+
+/// `/game/{name}/vod`, recent videos of every channel in a category.
+pub async fn vod(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = parse_game(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let game = get_game(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name,
+    )
+    .await?;
+
+    let videos = get_game_videos(
+        &state.cache,
+        state.config.videos_ttl,
+        &helix_client,
+        &token,
+        game.id.clone(),
+    )
+    .await?;
+
+    // every video is from a different channel, so each gets that channel's overrides
+    let items = videos
+        .iter()
+        .flat_map(|v| {
+            provider::twitch_items(
+                &state,
+                v.user_login.as_str(),
+                std::slice::from_ref(v),
+                &options.items,
+            )
+            .into_iter()
+            .map(|item| FeedItem {
+                title: format!("{}: {}", v.user_name, item.title),
+                category: Some(game.name.clone()),
+                ..item
+            })
+        })
+        .collect();
+
+    let feed = Feed {
+        title: options
+            .items
+            .locale
+            .unwrap_or(state.config.locale)
+            .vods_title(&game.name, "Twitch"),
+        link: game_link(&game),
+        image: Some(box_art_url(&game)),
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.videos_ttl),
+        items: provider::finish_items(&state, &options, items).await,
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `/game/{name}/clips`, the most viewed clips of a category, over the same windows as the clips of
+/// a channel.
+pub async fn clips(
+    Path(name): Path<String>,
+    Query(query): Query<WindowQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = parse_game(&name)?;
+    let window = ClipWindow::parse(&query)?;
+    let locale = query.locale(state.config.locale)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let game = get_game(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name,
+    )
+    .await?;
+
+    let mut clips = get_game_clips(
+        &state.cache,
+        state.config.videos_ttl,
+        &helix_client,
+        &token,
+        game.id.clone(),
+        window,
+    )
+    .await?;
+    clips.sort_by_key(|c| std::cmp::Reverse(c.view_count));
+
+    let feed = Feed {
+        title: locale.clips_title(&game.name, "Twitch"),
+        link: format!("{}/clips", game_link(&game)),
+        image: Some(box_art_url(&game)),
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.videos_ttl),
+        items: clips
+            .iter()
+            .map(|c| clips::attributed_item(c, locale))
+            .collect(),
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `/game/{name}/streams`, the live streams of a category with the most viewers right now.
+pub async fn streams(
+    Path(name): Path<String>,
+    Query(query): Query<LocaleQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = parse_game(&name)?;
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let game = get_game(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name,
+    )
+    .await?;
+
+    let streams = get_game_streams(
+        &state.cache,
+        state.config.live_ttl,
+        &helix_client,
+        &token,
+        game.id.clone(),
+    )
+    .await?;
+
+    for stream in &streams {
+        category::remember(&state, stream).await;
+    }
+
+    let feed = Feed {
+        title: locale.streams_title(&game.name, "Twitch"),
+        link: game_link(&game),
+        image: Some(box_art_url(&game)),
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.live_ttl),
+        // keyed by stream id, so a broadcast is one item however long it stays on top
+        items: streams
+            .iter()
+            .map(|s| live::stream_to_item(s, locale))
+            .collect(),
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// Game names are free text, so only blank and absurdly long ones are rejected up front.
+fn parse_game(raw: &str) -> Result<String, TwitchRssError> {
+    let name = raw.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_GAME_LEN || name.chars().any(char::is_control)
+    {
+        return Err(TwitchRssError::InvalidGame(raw.to_string()));
+    }
+
+    Ok(name.to_string())
+}
+
+fn game_link(game: &Game) -> String {
+    let mut url = Url::parse("https://www.twitch.tv/directory/category").expect("valid URL");
+    url.path_segments_mut()
+        .expect("https URLs have a path")
+        .push(&game.name);
+    url.to_string()
+}
+
+fn box_art_url(game: &Game) -> String {
+    game.box_art_url
+        .replace("{width}", "285")
+        .replace("{height}", "380")
+}
@@ -0,0 +1,135 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderValue, Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use axum::Router;
+use bytes::Buf;
+use h3::server::RequestStream;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tower::ServiceExt;
+
+use twitch_rss::config::Http3Config;
+
+/// How long clients may remember that HTTP/3 is available, in seconds
+const ALT_SVC_MAX_AGE: u32 = 24 * 60 * 60;
+
+/// Largest request body accepted, the same as axum's default limit over HTTP/1 and HTTP/2
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+/// Bind the QUIC endpoint, failing early if the certificate or port are unusable.
+pub fn bind(config: &Http3Config) -> Result<quinn::Endpoint, String> {
+    let certs = CertificateDer::pem_file_iter(&config.cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {}", config.cert_file.display(), e))?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_file)
+        .map_err(|e| format!("{}: {}", config.key_file.display(), e))?;
+
+    let mut tls = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("{}", e))?;
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+
+    let quic = QuicServerConfig::try_from(tls).map_err(|e| format!("{}", e))?;
+    let server = quinn::ServerConfig::with_crypto(Arc::new(quic));
+
+    quinn::Endpoint::server(server, SocketAddr::from(([0, 0, 0, 0], config.port)))
+        .map_err(|e| format!("UDP port {}: {}", config.port, e))
+}
+
+/// Value of the `Alt-Svc` header advertising the endpoint to TCP clients.
+pub fn alt_svc(port: u16) -> HeaderValue {
+    HeaderValue::from_str(&format!("h3=\":{}\"; ma={}", port, ALT_SVC_MAX_AGE))
+        .expect("valid header value")
+}
+
+/// Add [`alt_svc`] to every response of `app`.
+pub fn advertise(app: Router, port: u16) -> Router {
+    let value = alt_svc(port);
+    app.layer(axum::middleware::map_response(
+        move |mut response: Response<Body>| {
+            let value = value.clone();
+            async move {
+                response.headers_mut().insert(header::ALT_SVC, value);
+                response
+            }
+        },
+    ))
+}
+
+/// Serve `app` on `endpoint` until it is closed.
+pub async fn serve(endpoint: quinn::Endpoint, app: Router) {
+    while let Some(incoming) = endpoint.accept().await {
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Err(e) = connection(incoming, app).await {
+                println!("http3 connection failed: {}", e);
+            }
+        });
+    }
+}
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+async fn connection(incoming: quinn::Incoming, app: Router) -> Result<(), Error> {
+    let connection = incoming.await?;
+    let mut connection = h3::server::Connection::new(h3_quinn::Connection::new(connection)).await?;
+
+    while let Some(resolver) = connection.accept().await? {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let result = match resolver.resolve_request().await {
+                Ok((request, stream)) => respond(app, request, stream).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                println!("http3 request failed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn respond(
+    app: Router,
+    request: Request<()>,
+    mut stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+) -> Result<(), Error> {
+    // requests are small (the only bodies are admin JSON and EventSub notifications), so they
+    // are buffered instead of streamed into the router, up to the limit it would apply itself
+    let mut body = Vec::new();
+    let mut too_large = false;
+    while let Some(mut chunk) = stream.recv_data().await? {
+        if body.len() + chunk.remaining() > MAX_BODY {
+            too_large = true;
+            break;
+        }
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+
+    let response = if too_large {
+        StatusCode::PAYLOAD_TOO_LARGE.into_response()
+    } else {
+        app.oneshot(request.map(|()| Body::from(body)))
+            .await
+            .unwrap_or_else(|e| match e {})
+    };
+
+    let (parts, body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+
+    let body = axum::body::to_bytes(body, usize::MAX).await?;
+    if !body.is_empty() {
+        stream.send_data(body).await?;
+    }
+    stream.finish().await?;
+
+    Ok(())
+}
@@ -0,0 +1,412 @@
+use std::str::FromStr;
+
+/// Language of the text this service generates, like feed titles and item descriptions.
+///
+/// Titles and descriptions coming from Twitch are left as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+/// Values of `locale` and `FEED_LOCALE`.
+pub const LOCALES: [&str; 4] = ["en", "de", "fr", "es"];
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Self::En),
+            "de" => Ok(Self::De),
+            "fr" => Ok(Self::Fr),
+            "es" => Ok(Self::Es),
+            _ => Err(format!("{:?} is not one of {}", s, LOCALES.join(", "))),
+        }
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 4] = [Locale::En, Locale::De, Locale::Fr, Locale::Es];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Fr => "fr",
+            Self::Es => "es",
+        }
+    }
+
+    /// Title of the VOD feed of `name` on `platform`.
+    pub fn vods_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} VODs", name, platform),
+            Self::De => format!("{} {}-VODs", name, platform),
+            Self::Fr => format!("VOD {} de {}", platform, name),
+            Self::Es => format!("VOD de {} en {}", name, platform),
+        }
+    }
+
+    /// Title of the most viewed clips of `name` on `platform`, a channel, category or list of
+    /// channels.
+    pub fn clips_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("Top {} {} clips", name, platform),
+            Self::De => format!("Top-{}-Clips von {}", platform, name),
+            Self::Fr => format!("Meilleurs clips {} de {}", platform, name),
+            Self::Es => format!("Mejores clips de {} en {}", name, platform),
+        }
+    }
+
+    /// Title of the feed mixing the VODs and clips of `name` on `platform`.
+    pub fn vods_and_clips_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} VODs and clips", name, platform),
+            Self::De => format!("{} {}-VODs und Clips", name, platform),
+            Self::Fr => format!("VOD et clips {} de {}", platform, name),
+            Self::Es => format!("VOD y clips de {} en {}", name, platform),
+        }
+    }
+
+    /// Title of the feed of VODs of `name` on `platform` that are about to be deleted.
+    pub fn expiring_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} VODs expiring soon", name, platform),
+            Self::De => format!("Bald ablaufende {}-VODs von {}", platform, name),
+            Self::Fr => format!("VOD {} de {} bientôt expirées", platform, name),
+            Self::Es => format!("VOD de {} en {} que caducan pronto", name, platform),
+        }
+    }
+
+    /// Title of the feed of title and category changes of `name` on `platform`.
+    pub fn changes_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} channel changes", name, platform),
+            Self::De => format!("Änderungen am {}-Kanal von {}", platform, name),
+            Self::Fr => format!("Changements de la chaîne {} de {}", platform, name),
+            Self::Es => format!("Cambios del canal de {} en {}", name, platform),
+        }
+    }
+
+    /// Title of the schedule of `name` on `platform`.
+    pub fn schedule_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} schedule", name, platform),
+            Self::De => format!("{}-Zeitplan von {}", platform, name),
+            Self::Fr => format!("Programme {} de {}", platform, name),
+            Self::Es => format!("Horario de {} en {}", name, platform),
+        }
+    }
+
+    /// Title of the feed that has an item while `name` is live on `platform`.
+    pub fn live_title(self, name: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("{} {} live", name, platform),
+            Self::De => format!("{} live auf {}", name, platform),
+            Self::Fr => format!("{} en direct sur {}", name, platform),
+            Self::Es => format!("{} en vivo en {}", name, platform),
+        }
+    }
+
+    /// Title of the streams of the category `game` on `platform` with the most viewers.
+    pub fn streams_title(self, game: &str, platform: &str) -> String {
+        match self {
+            Self::En => format!("Live {} {} streams", game, platform),
+            Self::De => format!("{}-Livestreams von {}", platform, game),
+            Self::Fr => format!("Streams {} en direct de {}", platform, game),
+            Self::Es => format!("Streams en vivo de {} en {}", game, platform),
+        }
+    }
+
+    /// Title of the feed of the listed channels that are live.
+    pub fn live_channels_title(self) -> &'static str {
+        match self {
+            Self::En => "Live channels",
+            Self::De => "Live-Kanäle",
+            Self::Fr => "Chaînes en direct",
+            Self::Es => "Canales en vivo",
+        }
+    }
+
+    /// Word marking VODs among clips, as `[label] title`.
+    pub fn vod_label(self) -> &'static str {
+        match self {
+            Self::En | Self::De => "VOD",
+            Self::Fr => "Rediffusion",
+            Self::Es => "Emisión",
+        }
+    }
+
+    /// Word marking clips among VODs, as `[label] title`.
+    pub fn clip_label(self) -> &'static str {
+        // Twitch uses the English word in these languages too
+        match self {
+            Self::En | Self::De | Self::Fr | Self::Es => "Clip",
+        }
+    }
+
+    /// Single word marking the titles of sub-only videos, as `[label] title`.
+    pub fn sub_only_label(self) -> &'static str {
+        match self {
+            Self::En => "Sub-only",
+            Self::De => "Abo",
+            Self::Fr => "Abonnés",
+            Self::Es => "Suscriptores",
+        }
+    }
+
+    /// Notice at the top of the description of sub-only videos.
+    pub fn sub_only_notice(self) -> &'static str {
+        match self {
+            Self::En => "Only subscribers can watch this video",
+            Self::De => "Nur Abonnenten können dieses Video ansehen",
+            Self::Fr => "Seuls les abonnés peuvent regarder cette vidéo",
+            Self::Es => "Solo los suscriptores pueden ver este video",
+        }
+    }
+
+    /// Title of the item pinned for a running stream.
+    pub fn live_marker(self, title: &str) -> String {
+        let live = match self {
+            Self::En | Self::De => "LIVE",
+            Self::Fr => "EN DIRECT",
+            Self::Es => "EN VIVO",
+        };
+        // French puts a space before the colon
+        let colon = match self {
+            Self::Fr => " :",
+            _ => ":",
+        };
+        format!("\u{1f534} {}{} {}", live, colon, title)
+    }
+
+    /// Title of the item recorded for a stream starting, at `time`.
+    pub fn went_live(self, time: &str) -> String {
+        match self {
+            Self::En => format!("Went live at {}", time),
+            Self::De => format!("Live gegangen um {}", time),
+            Self::Fr => format!("En direct depuis {}", time),
+            Self::Es => format!("En vivo desde {}", time),
+        }
+    }
+
+    /// Title of the item of a running stream in live feeds.
+    pub fn is_live(self, user: &str, title: &str) -> String {
+        match self {
+            Self::En => format!("{} is live: {}", user, title),
+            Self::De => format!("{} ist live: {}", user, title),
+            Self::Fr => format!("{} est en direct : {}", user, title),
+            Self::Es => format!("{} está en vivo: {}", user, title),
+        }
+    }
+
+    /// What a running stream is streaming, for its description.
+    pub fn playing(self, game: &str, viewers: usize) -> String {
+        match self {
+            Self::En => format!("Playing {} for {} viewers", game, viewers),
+            Self::De => format!("Spielt {} für {} Zuschauer", game, viewers),
+            Self::Fr => format!("Joue à {} pour {} spectateurs", game, viewers),
+            Self::Es => format!("Jugando {} para {} espectadores", game, viewers),
+        }
+    }
+
+    /// Label of the category line of descriptions.
+    pub fn category(self) -> &'static str {
+        match self {
+            Self::En => "Category",
+            Self::De => "Kategorie",
+            Self::Fr => "Catégorie",
+            Self::Es => "Categoría",
+        }
+    }
+
+    /// Label of the title line of descriptions.
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::En => "Title",
+            Self::De => "Titel",
+            Self::Fr => "Titre",
+            Self::Es => "Título",
+        }
+    }
+
+    /// When a scheduled broadcast starts, for its description.
+    pub fn starts(self, time: &str) -> String {
+        match self {
+            Self::En => format!("Starts {}", time),
+            Self::De => format!("Beginn: {}", time),
+            Self::Fr => format!("Début : {}", time),
+            Self::Es => format!("Empieza: {}", time),
+        }
+    }
+
+    /// When a scheduled broadcast ends, for its description.
+    pub fn ends(self, time: &str) -> String {
+        match self {
+            Self::En => format!("Ends {}", time),
+            Self::De => format!("Ende: {}", time),
+            Self::Fr => format!("Fin : {}", time),
+            Self::Es => format!("Termina: {}", time),
+        }
+    }
+
+    /// Stands in for the category of a broadcast that has none.
+    pub fn no_category(self) -> &'static str {
+        match self {
+            Self::En => "none",
+            Self::De => "keine",
+            Self::Fr => "aucune",
+            Self::Es => "ninguna",
+        }
+    }
+
+    /// Note on videos deleted in `days` days, rounded up.
+    pub fn expires_in(self, days: u64) -> String {
+        match (self, days) {
+            (Self::En, 0 | 1) => "Expires in less than a day".to_string(),
+            (Self::En, _) => format!("Expires in ~{} days", days),
+            (Self::De, 0 | 1) => "Läuft in weniger als einem Tag ab".to_string(),
+            (Self::De, _) => format!("Läuft in ~{} Tagen ab", days),
+            (Self::Fr, 0 | 1) => "Expire dans moins d'un jour".to_string(),
+            (Self::Fr, _) => format!("Expire dans ~{} jours", days),
+            (Self::Es, 0 | 1) => "Caduca en menos de un día".to_string(),
+            (Self::Es, _) => format!("Caduca en ~{} días", days),
+        }
+    }
+
+    /// Note on the newest airing of a broadcast that aired `before` times earlier, first on
+    /// `first`.
+    pub fn reruns(self, before: usize, first: &str) -> String {
+        match (self, before) {
+            (Self::En, 1) => format!("Aired once before, on {}", first),
+            (Self::En, _) => format!("Aired {} times before, first on {}", before, first),
+            (Self::De, 1) => format!("Schon einmal gesendet, am {}", first),
+            (Self::De, _) => format!("Schon {}-mal gesendet, zuerst am {}", before, first),
+            (Self::Fr, 1) => format!("Déjà diffusé une fois, le {}", first),
+            (Self::Fr, _) => format!("Déjà diffusé {} fois, d'abord le {}", before, first),
+            (Self::Es, 1) => format!("Emitido una vez antes, el {}", first),
+            (Self::Es, _) => format!("Emitido {} veces antes, primero el {}", before, first),
+        }
+    }
+
+    /// Who made a clip and how often it was watched, for its description.
+    pub fn clipped_by(self, creator: &str, views: i64) -> String {
+        match self {
+            Self::En => format!("Clipped by {}, {}", creator, self.views(views)),
+            Self::De => format!("Geclippt von {}, {}", creator, self.views(views)),
+            Self::Fr | Self::Es => format!("Clip de {}, {}", creator, self.views(views)),
+        }
+    }
+
+    /// `secs` for people, like `3h 12m` in English. Seconds are left out past a minute.
+    pub fn duration(self, secs: u64) -> String {
+        let (hours, minutes) = (secs / 3600, secs % 3600 / 60);
+        let (h, m, s) = match self {
+            Self::En => ("h", "m", "s"),
+            Self::De => (" Std.", " Min.", " Sek."),
+            Self::Fr | Self::Es => (" h", " min", " s"),
+        };
+        match (hours, minutes) {
+            (0, 0) => format!("{}{}", secs, s),
+            (0, _) => format!("{}{}", minutes, m),
+            _ => format!("{}{} {}{}", hours, h, minutes, m),
+        }
+    }
+
+    /// `views` with thousands separators, like `1,234 views` in English.
+    pub fn views(self, views: i64) -> String {
+        let separator = match self {
+            Self::En => ",",
+            Self::De | Self::Es => ".",
+            // narrow no-break space
+            Self::Fr => "\u{202f}",
+        };
+        let digits = views.unsigned_abs().to_string();
+        let mut grouped = String::new();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(separator);
+            }
+            grouped.push(digit);
+        }
+
+        let (one, many) = match self {
+            Self::En => ("view", "views"),
+            Self::De => ("Aufruf", "Aufrufe"),
+            Self::Fr => ("vue", "vues"),
+            Self::Es => ("visualización", "visualizaciones"),
+        };
+        // French counts zero as singular too
+        let singular = views == 1 || (self == Self::Fr && views == 0);
+        format!("{} {}", grouped, if singular { one } else { many })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(Locale::En.duration(3 * 3600 + 12 * 60 + 5), "3h 12m");
+        assert_eq!(Locale::En.duration(3600), "1h 0m");
+        assert_eq!(Locale::En.duration(12 * 60 + 59), "12m");
+        assert_eq!(Locale::En.duration(45), "45s");
+        assert_eq!(Locale::En.duration(0), "0s");
+        assert_eq!(Locale::De.duration(3 * 3600 + 12 * 60), "3 Std. 12 Min.");
+        assert_eq!(Locale::Fr.duration(45), "45 s");
+    }
+
+    #[test]
+    fn groups_view_counts() {
+        assert_eq!(Locale::En.views(999), "999 views");
+        assert_eq!(Locale::En.views(1000), "1,000 views");
+        assert_eq!(Locale::En.views(1234567), "1,234,567 views");
+        assert_eq!(Locale::En.views(1), "1 view");
+        assert_eq!(Locale::De.views(1234567), "1.234.567 Aufrufe");
+        assert_eq!(Locale::Fr.views(0), "0 vue");
+    }
+
+    #[test]
+    fn counts_days_and_reruns() {
+        assert_eq!(Locale::En.expires_in(0), "Expires in less than a day");
+        assert_eq!(Locale::En.expires_in(3), "Expires in ~3 days");
+        assert_eq!(Locale::De.expires_in(3), "Läuft in ~3 Tagen ab");
+        assert_eq!(
+            Locale::En.reruns(1, "2024-06-01"),
+            "Aired once before, on 2024-06-01"
+        );
+        assert_eq!(
+            Locale::Fr.reruns(2, "2024-06-01"),
+            "Déjà diffusé 2 fois, d'abord le 2024-06-01"
+        );
+        assert_eq!(
+            Locale::En.clipped_by("Bar", 1234),
+            "Clipped by Bar, 1,234 views"
+        );
+    }
+
+    #[test]
+    fn names_feeds() {
+        assert_eq!(
+            Locale::En.clips_title("foo", "Twitch"),
+            "Top foo Twitch clips"
+        );
+        assert_eq!(
+            Locale::De.clips_title("foo", "Twitch"),
+            "Top-Twitch-Clips von foo"
+        );
+        assert_eq!(
+            Locale::En.schedule_title("foo", "Twitch"),
+            "foo Twitch schedule"
+        );
+        assert_eq!(
+            Locale::Es.live_title("foo", "Twitch"),
+            "foo en vivo en Twitch"
+        );
+    }
+}
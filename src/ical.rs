@@ -0,0 +1,162 @@
+use time::{OffsetDateTime, UtcOffset};
+
+/// Content lines longer than this many octets have to be folded
+const MAX_LINE: usize = 75;
+
+/// An event of an iCalendar document.
+pub struct Event {
+    pub uid: String,
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+    pub summary: String,
+    pub description: String,
+    pub url: String,
+    /// Repeats every week from `start` until this last occurrence
+    pub repeats_until: Option<OffsetDateTime>,
+    /// Occurrences of a weekly event that don't take place
+    pub skipped: Vec<OffsetDateTime>,
+}
+
+/// An RFC 5545 document with `events`, stamped with the current time.
+pub fn calendar(name: &str, events: &[Event]) -> String {
+    document(name, events, OffsetDateTime::now_utc())
+}
+
+fn document(name: &str, events: &[Event], stamp: OffsetDateTime) -> String {
+    let stamp = date_time(stamp);
+
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//twitch_rss//schedule//EN");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        line(&mut out, "BEGIN:VEVENT");
+        line(&mut out, &format!("UID:{}", escape(&event.uid)));
+        line(&mut out, &format!("DTSTAMP:{}", stamp));
+        line(&mut out, &format!("DTSTART:{}", date_time(event.start)));
+        line(&mut out, &format!("DTEND:{}", date_time(event.end)));
+        if let Some(until) = event.repeats_until {
+            line(
+                &mut out,
+                &format!("RRULE:FREQ=WEEKLY;UNTIL={}", date_time(until)),
+            );
+        }
+        for skipped in &event.skipped {
+            line(&mut out, &format!("EXDATE:{}", date_time(*skipped)));
+        }
+        line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        line(
+            &mut out,
+            &format!("DESCRIPTION:{}", escape(&event.description)),
+        );
+        line(&mut out, &format!("URL:{}", event.url));
+        line(&mut out, "END:VEVENT");
+    }
+    line(&mut out, "END:VCALENDAR");
+
+    out
+}
+
+/// UTC in the basic format, e.g. `20240601T180000Z`.
+fn date_time(date: OffsetDateTime) -> String {
+    let format = time::format_description::parse("[year][month][day]T[hour][minute][second]Z")
+        .expect("valid format description");
+    date.to_offset(UtcOffset::UTC)
+        .format(&format)
+        .unwrap_or_default()
+}
+
+/// Escape TEXT values. Line breaks of any kind become `\n`, a bare CR would end the content line.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\n', "\\n")
+}
+
+/// Append `content` as a CRLF terminated line, folded to at most [`MAX_LINE`] octets per line
+/// without splitting characters.
+fn line(out: &mut String, content: &str) {
+    let mut length = 0;
+    for c in content.chars() {
+        // continuation lines start with a space, which counts against their length
+        if length + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            length = 1;
+        }
+        out.push(c);
+        length += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape("a;b,c\\d\ne\r\nf\rg"), "a\\;b\\,c\\\\d\\ne\\nf\\ng");
+    }
+
+    #[test]
+    fn renders_a_weekly_event() {
+        let event = Event {
+            uid: "foo-saturday-1800@twitch.tv".to_string(),
+            start: datetime!(2024-06-01 18:00 UTC),
+            end: datetime!(2024-06-01 20:00 UTC),
+            summary: "Celeste: Speedruns".to_string(),
+            description: "Every Saturday, all chapters in one go.\nCome say hi, bring snacks; \
+                          it's going to be a long one"
+                .to_string(),
+            url: "https://www.twitch.tv/foo/schedule".to_string(),
+            repeats_until: Some(datetime!(2024-06-22 18:00 UTC)),
+            skipped: vec![datetime!(2024-06-15 18:00 UTC)],
+        };
+
+        let body = document(
+            "foo Twitch schedule",
+            &[event],
+            datetime!(2024-05-30 12:00 UTC),
+        );
+        assert_eq!(
+            body,
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//twitch_rss//schedule//EN\r\n\
+             CALSCALE:GREGORIAN\r\n\
+             X-WR-CALNAME:foo Twitch schedule\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:foo-saturday-1800@twitch.tv\r\n\
+             DTSTAMP:20240530T120000Z\r\n\
+             DTSTART:20240601T180000Z\r\n\
+             DTEND:20240601T200000Z\r\n\
+             RRULE:FREQ=WEEKLY;UNTIL=20240622T180000Z\r\n\
+             EXDATE:20240615T180000Z\r\n\
+             SUMMARY:Celeste: Speedruns\r\n\
+             DESCRIPTION:Every Saturday\\, all chapters in one go.\\nCome say hi\\, bring s\r\n \
+             nacks\\; it's going to be a long one\r\n\
+             URL:https://www.twitch.tv/foo/schedule\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        );
+        assert!(body.split("\r\n").all(|l| l.len() <= MAX_LINE));
+    }
+
+    #[test]
+    fn folds_without_splitting_characters() {
+        let mut out = String::new();
+        line(&mut out, &format!("SUMMARY:{}", "é".repeat(40)));
+        let lines = out.trim_end().split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        // 8 octets of name leave room for 33 two-octet characters
+        assert_eq!(lines[0].len(), 74);
+        assert_eq!(lines[1], format!(" {}", "é".repeat(7)));
+    }
+}
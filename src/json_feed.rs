@@ -0,0 +1,185 @@
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+
+use crate::config::ChannelMetadata;
+use crate::feed::{Feed, FeedItem};
+use crate::render::{self, FeedRenderer};
+
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// [JSON Feed 1.1](https://www.jsonfeed.org/version/1.1/) output.
+pub struct JsonFeedRenderer {
+    pub metadata: ChannelMetadata,
+}
+
+impl FeedRenderer for JsonFeedRenderer {
+    fn content_type(&self) -> &str {
+        "application/feed+json"
+    }
+
+    fn render(&self, feed: &Feed) -> Vec<u8> {
+        let document = JsonFeed {
+            version: VERSION,
+            title: &feed.title,
+            home_page_url: &feed.link,
+            feed_url: feed.self_link.as_deref(),
+            description: feed.description.as_deref(),
+            language: feed.language.as_deref(),
+            icon: feed.image.as_deref(),
+            favicon: self.metadata.icon.as_deref(),
+            hubs: render::websub_hub(feed)
+                .map(|url| JsonFeedHub {
+                    kind: "WebSub",
+                    url,
+                })
+                .into_iter()
+                .collect(),
+            items: feed.items.iter().map(JsonFeedItem::new).collect(),
+        };
+
+        serde_json::to_vec(&document).expect("JSON feeds only contain strings")
+    }
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feed_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    favicon: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hubs: Vec<JsonFeedHub<'a>>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedHub<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAttachment<'a> {
+    url: &'a str,
+    mime_type: &'a str,
+}
+
+impl<'a> JsonFeedItem<'a> {
+    fn new(item: &'a FeedItem) -> Self {
+        Self {
+            id: &item.id,
+            url: &item.link,
+            title: &item.title,
+            content_html: &item.description,
+            tags: item.categories().map(String::as_str).collect(),
+            date_published: item.published.format(&Rfc3339).ok(),
+            attachments: item
+                .enclosure
+                .iter()
+                .map(|e| JsonFeedAttachment {
+                    url: &e.url,
+                    mime_type: &e.mime_type,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::feed::Enclosure;
+
+    fn item(id: &str, enclosure: Option<Enclosure>) -> FeedItem {
+        FeedItem {
+            id: id.to_string(),
+            title: "Some stream".to_string(),
+            link: format!("https://www.twitch.tv/videos/{}", id),
+            description: "<p>Some stream</p>".to_string(),
+            published: datetime!(2024-06-01 20:00 +02:00),
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure,
+            tags: Vec::new(),
+            channel: Some("foo".to_string()),
+        }
+    }
+
+    #[test]
+    fn renders_json_feed_1_1() {
+        let feed = Feed {
+            title: "foo Twitch VODs".to_string(),
+            link: "https://www.twitch.tv/foo".to_string(),
+            image: None,
+            description: None,
+            language: None,
+            self_link: None,
+            hub: None,
+            ttl: None,
+            items: vec![
+                item("1", None),
+                item(
+                    "2",
+                    Some(Enclosure {
+                        url: "https://cdn.example.com/2.mp4".to_string(),
+                        mime_type: "video/mp4".to_string(),
+                    }),
+                ),
+            ],
+        };
+
+        let body = JsonFeedRenderer {
+            metadata: ChannelMetadata::default(),
+        }
+        .render(&feed);
+        let json = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["home_page_url"], "https://www.twitch.tv/foo");
+        for key in ["feed_url", "authors", "hubs", "favicon"] {
+            assert!(json.get(key).is_none(), "{}", key);
+        }
+
+        let first = &json["items"][0];
+        assert_eq!(first["id"], "1");
+        assert_eq!(first["url"], "https://www.twitch.tv/videos/1");
+        assert_eq!(first["date_published"], "2024-06-01T20:00:00+02:00");
+        for key in ["authors", "attachments", "tags"] {
+            assert!(first.get(key).is_none(), "{}", key);
+        }
+
+        assert_eq!(
+            json["items"][1]["attachments"],
+            serde_json::json!([{"url": "https://cdn.example.com/2.mp4", "mime_type": "video/mp4"}])
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use axum::extract::{OriginalUri, Path, Query, State};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::feed::FeedItem;
+use crate::options::{FeedOptions, FeedQuery};
+use crate::provider::{self, VideoProvider};
+use crate::render::Rendered;
+use crate::{render_feed, AppState, TwitchRssError};
+
+const KICK_API: &str = "https://kick.com/api/v2/channels";
+const MAX_SLUG_LEN: usize = 25;
+
+pub async fn channel(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let slug = parse_slug(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let feed = provider::vod_feed(&Kick, &state, &slug, &options).await?;
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// Kick channel slugs are lowercase letters, digits, '-' and '_'.
+fn parse_slug(raw: &str) -> Result<String, TwitchRssError> {
+    let slug = raw.trim().to_ascii_lowercase();
+
+    let valid = !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LEN
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(slug)
+    } else {
+        Err(TwitchRssError::InvalidChannel(format!(
+            "{:?} is not a valid Kick channel",
+            raw
+        )))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KickVideo {
+    #[serde(default)]
+    session_title: String,
+    created_at: String,
+    thumbnail: Option<KickThumbnail>,
+    video: KickVideoInfo,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KickThumbnail {
+    src: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KickVideoInfo {
+    uuid: String,
+}
+
+pub struct Kick;
+
+#[async_trait]
+impl VideoProvider for Kick {
+    fn platform(&self) -> &'static str {
+        "Kick"
+    }
+
+    fn channel_link(&self, channel: &str) -> String {
+        format!("https://kick.com/{}", channel)
+    }
+
+    async fn videos(
+        &self,
+        state: &AppState,
+        channel: &str,
+        _options: &FeedOptions,
+    ) -> Result<Vec<FeedItem>, TwitchRssError> {
+        let videos = state
+            .cache
+            .get_or_try_insert(
+                &format!("kick_videos:{}", channel),
+                state.config.videos_ttl,
+                || get_videos(state, channel),
+            )
+            .await?;
+
+        Ok(videos.iter().map(|v| video_to_item(channel, v)).collect())
+    }
+}
+
+async fn get_videos(state: &AppState, channel: &str) -> Result<Vec<KickVideo>, TwitchRssError> {
+    println!("getting kick videos for {}", channel);
+    let response = state
+        .client
+        .get(format!("{}/{}/videos", KICK_API, channel))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .map_err(|e| TwitchRssError::RequestError(format!("{}", e)))?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(TwitchRssError::UnknownChannel(channel.to_string()));
+    }
+
+    response
+        .error_for_status()
+        .map_err(|e| TwitchRssError::RequestError(format!("{}", e)))?
+        .json()
+        .await
+        .map_err(|e| TwitchRssError::RequestError(format!("{}", e)))
+}
+
+fn video_to_item(channel: &str, input: &KickVideo) -> FeedItem {
+    let link = format!("https://kick.com/{}/videos/{}", channel, input.video.uuid);
+
+    let thumbnail = input.thumbnail.as_ref().map(|t| t.src.clone());
+    let mut description = match &thumbnail {
+        Some(src) => format!("<a href=\"{}\"><img src=\"{}\" /></a>", link, src),
+        None => String::new(),
+    };
+
+    // same as the twitch description, the title keeps the description changing with the item
+    if !description.is_empty() {
+        description.push_str("<br />");
+    }
+    description.push_str(&input.session_title);
+
+    FeedItem {
+        id: input.video.uuid.clone(),
+        title: input.session_title.clone(),
+        link,
+        description,
+        published: parse_created_at(&input.created_at).unwrap_or(OffsetDateTime::UNIX_EPOCH),
+        sub_only: false,
+        duration: None,
+        thumbnail,
+        views: None,
+        category: None,
+        enclosure: None,
+        tags: Vec::new(),
+        channel: Some(channel.to_string()),
+    }
+}
+
+/// Kick reports times as `2024-03-01 19:02:03` in UTC.
+fn parse_created_at(input: &str) -> Option<OffsetDateTime> {
+    let format = time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]")
+        .expect("format description is valid");
+
+    time::PrimitiveDateTime::parse(input, &format)
+        .ok()
+        .map(|t| t.assume_utc())
+}
@@ -0,0 +1,61 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use twitch_api2::helix::videos::Video;
+
+use crate::duration;
+use crate::feed::{self, ThumbnailSize};
+use crate::options::VideoFilter;
+use crate::{login, provider, AppState, TwitchRssError};
+
+/// A single video, for consumers that don't want to parse a feed.
+#[derive(Serialize)]
+pub struct VideoSummary {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// Length in seconds
+    pub duration: Option<u64>,
+    /// RFC 3339 timestamp
+    pub published: String,
+    pub thumbnail: String,
+}
+
+impl VideoSummary {
+    pub fn new(video: &Video, size: ThumbnailSize) -> Self {
+        Self {
+            id: video.id.to_string(),
+            title: video.title.clone(),
+            url: video.url.clone(),
+            duration: duration::parse(&video.duration),
+            published: video
+                .created_at
+                .to_utc()
+                .format(&Rfc3339)
+                .unwrap_or_default(),
+            thumbnail: feed::thumbnail_url(video, size),
+        }
+    }
+}
+
+/// `/channel/{name}/vod/latest.json`, the newest video of a channel.
+pub async fn latest(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<VideoSummary>, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    let videos = provider::twitch_videos(&state, &name, &VideoFilter::default()).await?;
+    let video = videos
+        .iter()
+        .max_by_key(|v| v.created_at.to_utc())
+        .ok_or_else(|| TwitchRssError::NoVideos(name.clone()))?;
+
+    let size = state
+        .overrides
+        .get(&name)
+        .thumbnail_size
+        .unwrap_or_default();
+    Ok(Json(VideoSummary::new(video, size)))
+}
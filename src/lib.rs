@@ -0,0 +1,1197 @@
+use core::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::Router;
+use cached::proc_macro::cached;
+use reqwest::Client as ReqwestClient;
+use twitch_api2::helix::channels::ChannelInformation;
+use twitch_api2::helix::clips::{get_clips, Clip};
+use twitch_api2::helix::games::{get_games, Game};
+use twitch_api2::helix::schedule::get_channel_stream_schedule;
+use twitch_api2::helix::search::search_channels;
+use twitch_api2::helix::streams::{get_streams, Stream};
+use twitch_api2::helix::teams::get_teams::{self, Team};
+use twitch_api2::helix::users::{get_users, User};
+use twitch_api2::helix::videos::{get_videos, Video};
+use twitch_api2::helix::{ClientRequestError, HelixClient, HelixRequestGetError};
+use twitch_api2::twitch_oauth2::{AppAccessToken, ClientId, ClientSecret};
+use twitch_api2::types::{CategoryId, Nickname, UserId, VideoId};
+
+use cache::Cache;
+use clips::ClipWindow;
+use config::{Config, ConfigError, HttpConfig};
+use feed::FeedNaming;
+use options::{FeedOptions, FeedQuery, FormatQuery, VideoFilter};
+use overrides::Overrides;
+use render::Rendered;
+use schedule::ScheduledSegment;
+#[cfg(feature = "templates")]
+use templates::Templates;
+#[cfg(feature = "admin")]
+use tracked::TrackedChannels;
+
+pub use cache::{CacheStore, MemoryStore};
+pub use feed::{Enclosure, Feed, FeedItem};
+pub use redis::RedisStore;
+pub use render::{FeedRenderer, Renderers};
+
+#[cfg(feature = "admin")]
+mod admin;
+mod api;
+#[cfg(feature = "admin")]
+mod auth;
+mod cache;
+mod category;
+mod clips;
+mod combined;
+mod conditional;
+pub mod config;
+mod crawlers;
+mod duration;
+#[cfg(feature = "eventsub")]
+mod eventsub;
+mod expiry;
+mod feed;
+mod game;
+mod i18n;
+mod ical;
+mod json_feed;
+mod kick;
+mod latest;
+mod live;
+mod login;
+#[cfg(feature = "media")]
+mod media;
+mod multi;
+#[cfg(feature = "admin")]
+mod notify;
+#[cfg(feature = "admin")]
+mod opml;
+mod options;
+mod overrides;
+mod preview;
+mod provider;
+mod redis;
+pub mod render;
+mod response_cache;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod tags;
+mod team;
+#[cfg(feature = "templates")]
+mod templates;
+mod timezone;
+#[cfg(feature = "admin")]
+mod tracked;
+mod video;
+#[cfg(feature = "eventsub")]
+mod websub;
+
+#[derive(Debug)]
+pub enum TwitchRssError {
+    Token(String),
+    InvalidChannel(String),
+    InvalidQuery(String),
+    UnknownChannel(String),
+    NoVideos(String),
+    InvalidVideo(String),
+    UnknownVideo(String),
+    InvalidGame(String),
+    UnknownGame(String),
+    InvalidTeam(String),
+    UnknownTeam(String),
+    Unauthorized,
+    RequestError(String),
+    #[cfg(feature = "admin")]
+    AdminUnauthorized,
+    #[cfg(feature = "admin")]
+    NotTracked(String),
+    #[cfg(feature = "admin")]
+    Storage(String),
+    #[cfg(feature = "templates")]
+    Template(String),
+    #[cfg(feature = "scripting")]
+    Script(String),
+}
+
+impl fmt::Display for TwitchRssError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Self::Token(e) => write!(f, "Token({})", e),
+            Self::InvalidChannel(e) => write!(f, "InvalidChannel({})", e),
+            Self::InvalidQuery(e) => write!(f, "InvalidQuery({})", e),
+            Self::UnknownChannel(ch) => write!(f, "UnknownChannel({})", ch),
+            Self::NoVideos(ch) => write!(f, "NoVideos({})", ch),
+            Self::InvalidVideo(id) => write!(f, "InvalidVideo({})", id),
+            Self::UnknownVideo(id) => write!(f, "UnknownVideo({})", id),
+            Self::InvalidGame(name) => write!(f, "InvalidGame({})", name),
+            Self::UnknownGame(name) => write!(f, "UnknownGame({})", name),
+            Self::InvalidTeam(name) => write!(f, "InvalidTeam({})", name),
+            Self::UnknownTeam(name) => write!(f, "UnknownTeam({})", name),
+            Self::Unauthorized => write!(f, "Unauthorized"),
+            Self::RequestError(e) => write!(f, "RequestError({})", e),
+            #[cfg(feature = "admin")]
+            Self::AdminUnauthorized => write!(f, "AdminUnauthorized"),
+            #[cfg(feature = "admin")]
+            Self::NotTracked(ch) => write!(f, "NotTracked({})", ch),
+            #[cfg(feature = "admin")]
+            Self::Storage(e) => write!(f, "Storage({})", e),
+            #[cfg(feature = "templates")]
+            Self::Template(e) => write!(f, "Template({})", e),
+            #[cfg(feature = "scripting")]
+            Self::Script(e) => write!(f, "Script({})", e),
+        }
+    }
+}
+
+impl std::error::Error for TwitchRssError {}
+
+impl IntoResponse for TwitchRssError {
+    fn into_response(self) -> axum::response::Response {
+        let err_string = format!("{}", self);
+        let status = match self {
+            Self::Token(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::InvalidChannel(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownChannel(_) => StatusCode::NOT_FOUND,
+            Self::NoVideos(_) => StatusCode::NOT_FOUND,
+            Self::InvalidVideo(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownVideo(_) => StatusCode::NOT_FOUND,
+            Self::InvalidGame(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownGame(_) => StatusCode::NOT_FOUND,
+            Self::InvalidTeam(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownTeam(_) => StatusCode::NOT_FOUND,
+            Self::Unauthorized => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::RequestError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "admin")]
+            Self::AdminUnauthorized => StatusCode::UNAUTHORIZED,
+            #[cfg(feature = "admin")]
+            Self::NotTracked(_) => StatusCode::NOT_FOUND,
+            #[cfg(feature = "admin")]
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "templates")]
+            Self::Template(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "scripting")]
+            Self::Script(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, err_string).into_response()
+    }
+}
+
+/// `/channel/{name}/id`, just the user id, from before `/api/channel/{name}`.
+async fn world(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<String, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        name.into(),
+    )
+    .await?;
+
+    Ok(format!("{}", user_id))
+}
+
+async fn channel(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, TwitchRssError> {
+    let feed = published_channel_feed(&state, &name, query, &uri).await?;
+
+    render_negotiated(&state, &format, &headers, &uri, feed)
+}
+
+/// `/channel/id/{user_id}/vod`, the same feed as [`channel`] for a channel given by its user id,
+/// which unlike the login never changes.
+async fn channel_by_id(
+    Path(user_id): Path<String>,
+    Query(query): Query<FeedQuery>,
+    Query(format): Query<FormatQuery>,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, TwitchRssError> {
+    let user_id = login::parse_user_id(&user_id)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user = get_user_by_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        user_id.into(),
+    )
+    .await?;
+
+    // the rest of the feed finds the user by its current login, without looking the id up again
+    let login = user.login.to_string();
+    let ttl = state.config.user_id_ttl;
+    state
+        .cache
+        .set(&cache::user_id_key(&login), &user.id, ttl)
+        .await;
+    state.cache.set(&cache::user_key(&login), &user, ttl).await;
+
+    let feed = channel_feed(&state, &login, query).await?;
+
+    render_negotiated(&state, &format, &headers, &uri, feed)
+}
+
+/// Render `feed` in the format asked for by `format` or the `Accept` header.
+fn render_negotiated(
+    state: &AppState,
+    format: &FormatQuery,
+    headers: &HeaderMap,
+    uri: &Uri,
+    mut feed: Feed,
+) -> Result<impl IntoResponse, TwitchRssError> {
+    let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+    let renderer = state
+        .renderers
+        .negotiate(format.format.as_deref(), accept)?;
+
+    feed.self_link = self_link(state, uri);
+
+    Ok((
+        [(header::VARY, "Accept")],
+        Rendered::new(renderer.as_ref(), &feed),
+    ))
+}
+
+/// The same feed as [`channel`], rendered as Atom.
+async fn channel_atom(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let feed = published_channel_feed(&state, &name, query, &uri).await?;
+
+    Ok(render_feed_as(&state, "atom", &uri, feed))
+}
+
+/// The same feed as [`channel`], rendered as JSON Feed.
+async fn channel_json(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let feed = published_channel_feed(&state, &name, query, &uri).await?;
+
+    Ok(render_feed_as(&state, "json", &uri, feed))
+}
+
+/// [`channel_feed`] served at `uri`, advertising the WebSub hub if the hub is told about it.
+#[cfg_attr(not(feature = "eventsub"), allow(unused_variables))]
+async fn published_channel_feed(
+    state: &AppState,
+    name: &str,
+    query: FeedQuery,
+    uri: &Uri,
+) -> Result<Feed, TwitchRssError> {
+    #[cfg_attr(not(feature = "eventsub"), allow(unused_mut))]
+    let mut feed = channel_feed(state, name, query).await?;
+    #[cfg(feature = "eventsub")]
+    {
+        feed.hub = websub::hub(state, &login::parse_login(name)?, uri).await;
+    }
+    Ok(feed)
+}
+
+async fn channel_feed(
+    state: &AppState,
+    name: &str,
+    query: FeedQuery,
+) -> Result<Feed, TwitchRssError> {
+    let name = login::parse_login(name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let (mut feed, user, channel) = tokio::try_join!(
+        provider::vod_feed(&provider::Twitch, state, &name, &options),
+        provider::twitch_user(state, &name),
+        provider::twitch_channel(state, &name),
+    )?;
+    let locale = options.items.locale.unwrap_or(state.config.locale);
+    feed.title = locale.vods_title(user.display_name.as_str(), "Twitch");
+    feed.description = user.description.filter(|d| !d.is_empty());
+    feed.image = user.profile_image_url;
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming {
+            display_name: user.display_name.as_str(),
+            ..FeedNaming::twitch(&name, "VODs")
+        },
+    );
+    // Twitch uses `other` for channels without a single language
+    feed.language = channel
+        .map(|c| c.broadcaster_language)
+        .filter(|l| !l.is_empty() && l != "other");
+
+    let overrides = state.overrides.get(&name);
+    if let Some(title) = overrides.title {
+        feed.title = title;
+    }
+    if let Some(limit) = overrides.limit {
+        feed.items.truncate(limit);
+    }
+
+    // added after the limit so they never push a video out
+    #[cfg(feature = "eventsub")]
+    eventsub::add_went_live(
+        state,
+        &name,
+        &mut feed.items,
+        locale,
+        options
+            .time_zone
+            .as_ref()
+            .unwrap_or(&state.config.time_zone),
+    )
+    .await;
+    if options.live {
+        if let Some(item) = live::pinned_item(state, &name, locale).await? {
+            // the running stream may have been recorded going live too
+            feed.items.retain(|i| i.id != item.id);
+            feed.items.insert(0, item);
+        }
+    }
+
+    #[cfg(feature = "admin")]
+    state.tracked.record_refresh(&name, feed.items.len()).await;
+
+    Ok(feed)
+}
+
+/// Render `feed` as RSS, linking to itself at `uri`.
+fn render_feed(state: &AppState, uri: &Uri, feed: Feed) -> Rendered {
+    render_feed_as(state, "rss", uri, feed)
+}
+
+fn render_feed_as(state: &AppState, format: &str, uri: &Uri, mut feed: Feed) -> Rendered {
+    let renderer = state
+        .renderers
+        .get(format)
+        .expect("built-in renderers are always registered");
+
+    feed.self_link = self_link(state, uri);
+    Rendered::new(renderer.as_ref(), &feed)
+}
+
+/// Public URL of the request for `uri`, known if `BASE_URL` says where this server is reachable.
+fn self_link(state: &AppState, uri: &Uri) -> Option<String> {
+    state
+        .config
+        .public_url(uri.path_and_query().map_or(uri.path(), |p| p.as_str()))
+}
+
+/// The whole feed service as a router with its own state, ready to be served or nested into
+/// another axum application. Background tasks are spawned, so this has to be called from within a
+/// Tokio runtime.
+///
+/// The cache is kept in memory, or in Redis if `CACHE_REDIS_URL` is set.
+pub fn router(config: Config) -> Result<Router, Vec<ConfigError>> {
+    let renderers = Renderers::new(config.channel.clone());
+    let store: Arc<dyn CacheStore> = match config.redis.clone() {
+        Some(redis) => Arc::new(RedisStore::new(redis)),
+        None => Arc::new(MemoryStore::default()),
+    };
+    router_with(config, renderers, store)
+}
+
+/// [`router`] with other output formats and another cache. `renderers` is usually
+/// [`Renderers::new`] with more registered, which are then chosen by `format=` and `Accept` like
+/// the built-in ones. Twitch responses and rendered feeds are kept in `store`, which can live
+/// outside the process to be shared by several instances.
+pub fn router_with(
+    config: Config,
+    renderers: Renderers,
+    store: Arc<dyn CacheStore>,
+) -> Result<Router, Vec<ConfigError>> {
+    // things the configuration points at, checked together so every problem is reported at once
+    let mut errors = Vec::new();
+    let client = ConfigError::check(
+        &mut errors,
+        "HTTP_*",
+        "check the HTTP client settings",
+        build_http_client(&config.http),
+    );
+    #[cfg(feature = "admin")]
+    let tracked = ConfigError::check(
+        &mut errors,
+        "TRACKED_CHANNELS_FILE",
+        "a JSON file written by this server, or a path where it can create one",
+        TrackedChannels::load(config.tracked_channels_file.clone()),
+    );
+    let overrides = ConfigError::check(
+        &mut errors,
+        "CHANNEL_OVERRIDES_FILE",
+        "a JSON object of per-channel settings",
+        Overrides::load(config.channel_overrides_file.as_deref()),
+    );
+    #[cfg(feature = "templates")]
+    let templates = ConfigError::check(
+        &mut errors,
+        "TEMPLATE_DIR",
+        "fix the template syntax error",
+        config
+            .template_dir
+            .clone()
+            .map(|dir| Templates::load(dir).map(Arc::new))
+            .transpose(),
+    );
+    #[cfg(feature = "scripting")]
+    let script = ConfigError::check(
+        &mut errors,
+        "ITEM_SCRIPT",
+        "fix the script syntax error",
+        config
+            .item_script
+            .as_deref()
+            .map(|path| script::ItemScript::load(path).map(Arc::new))
+            .transpose(),
+    );
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    #[cfg(feature = "templates")]
+    let templates = templates.expect("checked above");
+    #[cfg(feature = "templates")]
+    if let Some(t) = &templates {
+        t.clone().watch();
+    }
+
+    #[cfg(feature = "eventsub")]
+    let eventsub = config.eventsub.clone().map(|c| {
+        Arc::new(eventsub::EventSub::new(
+            c.callback_url,
+            c.secret,
+            c.zero_poll,
+            c.live_items,
+        ))
+    });
+
+    let state = AppState {
+        client: client.expect("checked above"),
+        client_id: config.client_id.clone(),
+        client_secret: config.client_secret.clone(),
+        #[cfg(feature = "admin")]
+        tracked: Arc::new(tracked.expect("checked above")),
+        #[cfg(feature = "templates")]
+        templates,
+        overrides: Arc::new(overrides.expect("checked above")),
+        renderers: Arc::new(renderers),
+        cache: Cache::new(store),
+        #[cfg(feature = "scripting")]
+        script: script.expect("checked above"),
+        #[cfg(feature = "eventsub")]
+        eventsub,
+        #[cfg(feature = "media")]
+        media: config
+            .media
+            .as_ref()
+            .map(|m| Arc::new(media::Media::new(m))),
+        config: Arc::new(config),
+    };
+    let config = state.config.clone();
+
+    let channel = Router::new()
+        .route("/{name}", get(preview::page))
+        .route("/{name}/vod", get(channel))
+        .route("/id/{user_id}/vod", get(channel_by_id))
+        .route("/{name}/vod.atom", get(channel_atom))
+        .route("/{name}/vod.json", get(channel_json))
+        .route("/{name}/vod/latest.json", get(latest::latest))
+        .route("/{name}/vod/expiring", get(expiry::expiring))
+        .route("/{name}/id", get(world))
+        .route("/{name}/all", get(combined::all))
+        .route("/{name}/clips", get(clips::channel))
+        .route("/{name}/live", get(live::channel))
+        .route("/{name}/schedule", get(schedule::feed))
+        .route("/{name}/schedule.ics", get(schedule::calendar));
+
+    #[cfg(feature = "eventsub")]
+    let channel = if state.eventsub.is_some() {
+        channel.route("/{name}/changes", get(eventsub::changes))
+    } else {
+        channel
+    };
+
+    let channel = channel.route_layer(axum::middleware::from_fn(login::canonical_redirect));
+
+    let kick = Router::new().route("/{name}/vod", get(kick::channel));
+
+    let feeds = Router::new()
+        .nest("/channel", channel)
+        .nest("/kick", kick)
+        .route("/game/{name}/vod", get(game::vod))
+        .route("/multi/{names}/vod", get(multi::vod))
+        .route("/multi/vod", post(multi::batch))
+        .route("/team/{name}/vod", get(team::vod))
+        .route("/game/{name}/clips", get(game::clips))
+        .route("/game/{name}/streams", get(game::streams))
+        .route("/channels/clips", get(clips::multi))
+        .route("/video/{id}", get(video::feed))
+        .route("/video/{id}/json", get(video::json))
+        .route("/live", get(live::feed))
+        .route("/live.json", get(live::json))
+        .route("/api/channel/{name}", get(api::channel))
+        .route("/api/channel/{name}/videos", get(api::channel_videos));
+
+    // compact aliases for readers where the full paths are awkward to type
+    let feeds = if config.short_routes {
+        feeds
+            .route("/c/{name}", get(self::channel))
+            .route("/l/{name}", get(live::channel))
+    } else {
+        feeds
+    };
+    let feeds = feeds
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            response_cache::serve,
+        ))
+        .route_layer(axum::middleware::from_fn(conditional::not_modified))
+        .route_layer(axum::middleware::from_fn(crawlers::reject_probes));
+    let feeds = if config.noindex {
+        feeds.layer(axum::middleware::map_response(crawlers::noindex))
+    } else {
+        feeds
+    };
+
+    let app = Router::new()
+        .route("/robots.txt", get(crawlers::robots_txt))
+        .merge(feeds);
+
+    // operator routes don't exist at all unless they can be protected, the OPML export is one
+    // too since it lists every tracked channel
+    #[cfg(feature = "admin")]
+    let app = match config.admin_token.clone() {
+        Some(token) => app
+            .merge(auth::require_bearer(
+                Router::new().route("/opml", get(opml::export)),
+                token.clone(),
+            ))
+            .nest("/admin", admin::router(token)),
+        None => app,
+    };
+
+    #[cfg(feature = "eventsub")]
+    let app = if state.eventsub.is_some() {
+        app.route("/eventsub/callback", post(eventsub::callback))
+    } else {
+        app
+    };
+
+    #[cfg(feature = "admin")]
+    tokio::spawn(admin::watch_tracked(state.clone()));
+    #[cfg(feature = "eventsub")]
+    tokio::spawn(eventsub::subscribe_tracked(state.clone()));
+
+    Ok(app.with_state(state))
+}
+
+/// The client shared by every outgoing request. Pooling defaults suit steady traffic, so they can
+/// be tuned for bursty readers or long idle periods.
+fn build_http_client(config: &HttpConfig) -> reqwest::Result<ReqwestClient> {
+    let mut builder = ReqwestClient::builder();
+
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(timeout) = config.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(interval) = config.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    // Helix speaks HTTP/2, skipping the upgrade lets many requests share a single connection
+    if config.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+
+    builder.build()
+}
+
+#[derive(Clone)]
+struct AppState {
+    client: ReqwestClient,
+    client_id: ClientId,
+    client_secret: ClientSecret,
+    #[cfg(feature = "admin")]
+    tracked: Arc<TrackedChannels>,
+    #[cfg(feature = "templates")]
+    templates: Option<Arc<Templates>>,
+    overrides: Arc<Overrides>,
+    renderers: Arc<Renderers>,
+    cache: Cache,
+    config: Arc<Config>,
+    #[cfg(feature = "scripting")]
+    script: Option<Arc<script::ItemScript>>,
+    #[cfg(feature = "eventsub")]
+    eventsub: Option<Arc<eventsub::EventSub>>,
+    #[cfg(feature = "media")]
+    media: Option<Arc<media::Media>>,
+}
+
+fn handle_helix_error(err: ClientRequestError<reqwest::Error>) -> TwitchRssError {
+    match err {
+        ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+            status, ..
+        }) if status == reqwest::StatusCode::UNAUTHORIZED => TwitchRssError::Unauthorized,
+        e => TwitchRssError::RequestError(format!("{}", e)),
+    }
+}
+
+#[cached(
+    time = 1200,
+    result = true,
+    key = "(ClientId, ClientSecret)",
+    convert = "{ (client_id.clone(), client_secret.clone()) }"
+)]
+async fn get_token(
+    client: &ReqwestClient,
+    client_id: ClientId,
+    client_secret: ClientSecret,
+) -> Result<AppAccessToken, TwitchRssError> {
+    println!("getting token");
+    match AppAccessToken::get_app_access_token(client, client_id, client_secret, vec![]).await {
+        Ok(t) => Ok(t),
+        Err(e) => {
+            println!("{:?}", e);
+            Err(TwitchRssError::Token(format!("{}", e)))
+        }
+    }
+}
+
+async fn get_user_id(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_name: Nickname,
+) -> Result<UserId, TwitchRssError> {
+    let key = cache::user_id_key(user_name.as_str());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting user {}", user_name);
+            let maybe_channel = client
+                .get_channel_from_login(user_name.clone(), token)
+                .await
+                .map_err(handle_helix_error)?;
+
+            match maybe_channel {
+                Some(c) => Ok(c.broadcaster_id),
+                None => Err(unknown_channel(cache, ttl, client, token, &user_name).await),
+            }
+        })
+        .await
+}
+
+async fn get_user(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_name: Nickname,
+) -> Result<User, TwitchRssError> {
+    let key = cache::user_key(user_name.as_str());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting user details for {}", user_name);
+            let user_request = get_users::GetUsersRequest::builder()
+                .login(vec![user_name.clone()])
+                .build();
+
+            let user = client
+                .req_get(user_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data
+                .into_iter()
+                .next();
+
+            match user {
+                Some(user) => Ok(user),
+                None => Err(unknown_channel(cache, ttl, client, token, &user_name).await),
+            }
+        })
+        .await
+}
+
+async fn get_user_by_id(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_id: UserId,
+) -> Result<User, TwitchRssError> {
+    let key = cache::user_by_id_key(&user_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting user details for id {}", user_id);
+            let user_request = get_users::GetUsersRequest::builder()
+                .id(vec![user_id.clone()])
+                .build();
+
+            client
+                .req_get(user_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data
+                .into_iter()
+                .next()
+                .ok_or_else(|| TwitchRssError::UnknownChannel(user_id.to_string()))
+        })
+        .await
+}
+
+/// Channel information of `user_name`, such as its language.
+async fn get_channel(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_name: Nickname,
+) -> Result<ChannelInformation, TwitchRssError> {
+    let key = cache::channel_key(user_name.as_str());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting channel information for {}", user_name);
+            let channel = client
+                .get_channel_from_login(user_name.clone(), token)
+                .await
+                .map_err(handle_helix_error)?;
+
+            match channel {
+                Some(channel) => Ok(channel),
+                None => Err(unknown_channel(cache, ttl, client, token, &user_name).await),
+            }
+        })
+        .await
+}
+
+/// How many near-matches an unknown channel's 404 suggests.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The error for `user_name` not being a channel, suggesting channels Twitch finds for it so a
+/// typo in a feed URL isn't a dead end.
+///
+/// Failing to search is not worth reporting over the 404 itself.
+async fn unknown_channel(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_name: &Nickname,
+) -> TwitchRssError {
+    let key = cache::suggestions_key(user_name.as_str());
+    let suggestions = cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("searching channels like {}", user_name);
+            let request = search_channels::SearchChannelsRequest::builder()
+                .query(user_name.as_str())
+                // one spare in case the search still returns the login itself
+                .first(MAX_SUGGESTIONS + 1)
+                .build();
+
+            let channels = client
+                .req_get(request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok::<_, TwitchRssError>(
+                channels
+                    .into_iter()
+                    .map(|c| c.broadcaster_login.to_string())
+                    .filter(|login| login != user_name.as_str())
+                    .take(MAX_SUGGESTIONS)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .await
+        .unwrap_or_default();
+
+    login::unknown(user_name.as_str(), &suggestions)
+}
+
+async fn get_channel_tags(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_id: UserId,
+) -> Result<Vec<String>, TwitchRssError> {
+    let key = cache::channel_tags_key(&user_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting channel tags for {}", user_id);
+            let request = tags::GetChannelTagsRequest {
+                broadcaster_id: user_id.clone(),
+            };
+
+            let channels = client
+                .req_get(request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(channels
+                .into_iter()
+                .next()
+                .map(|c| c.tags)
+                .unwrap_or_default())
+        })
+        .await
+}
+
+async fn get_user_videos(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_id: UserId,
+    filter: &VideoFilter,
+) -> Result<Vec<Video>, TwitchRssError> {
+    let generation = cache::videos_generation(cache, &user_id).await;
+    let key = cache::videos_key(&user_id, generation, &filter.key());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting {} videos for {}", filter.key(), user_id);
+            let mut videos = Vec::new();
+            let mut after = None;
+            for _ in 0..filter.pages.unwrap_or(1) {
+                let video_request = get_videos::GetVideosRequest::builder()
+                    .user_id(user_id.clone())
+                    .type_(filter.video_type.clone())
+                    .first(match filter.pages {
+                        Some(_) => Some(options::MAX_LIMIT),
+                        None => filter.first,
+                    })
+                    .period(filter.period.clone())
+                    .sort(filter.sort.clone())
+                    .language(filter.helix_language().cloned())
+                    .after(after)
+                    .build();
+
+                let response = client
+                    .req_get(video_request, token)
+                    .await
+                    .map_err(handle_helix_error)?;
+
+                videos.extend(response.data);
+                after = match response.pagination {
+                    Some(cursor) => Some(cursor),
+                    None => break,
+                };
+            }
+
+            Ok(videos)
+        })
+        .await
+}
+
+/// The category called `name`, which Helix matches case-insensitively.
+async fn get_game(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    name: String,
+) -> Result<Game, TwitchRssError> {
+    let key = cache::game_key(&name);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting game {}", name);
+            let game_request = get_games::GetGamesRequest::builder()
+                .name(vec![name.clone()])
+                .build();
+
+            client
+                .req_get(game_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data
+                .into_iter()
+                .next()
+                .ok_or_else(|| TwitchRssError::UnknownGame(name.clone()))
+        })
+        .await
+}
+
+async fn get_game_videos(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    game_id: CategoryId,
+) -> Result<Vec<Video>, TwitchRssError> {
+    let key = cache::game_videos_key(&game_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting videos for game {}", game_id);
+            let video_request = get_videos::GetVideosRequest::builder()
+                .game_id(game_id.clone())
+                .build();
+
+            let videos = client
+                .req_get(video_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(videos)
+        })
+        .await
+}
+
+/// Live streams in `game_id`, Helix puts the most watched first.
+async fn get_game_streams(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    game_id: CategoryId,
+) -> Result<Vec<Stream>, TwitchRssError> {
+    let key = cache::game_streams_key(&game_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting streams for game {}", game_id);
+            let stream_request = get_streams::GetStreamsRequest::builder()
+                .game_id(vec![game_id.clone()])
+                .build();
+
+            let streams = client
+                .req_get(stream_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(streams)
+        })
+        .await
+}
+
+/// Upcoming broadcasts on the schedule of `user_id`.
+async fn get_schedule(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_id: UserId,
+) -> Result<Vec<ScheduledSegment>, TwitchRssError> {
+    let key = cache::schedule_key(&user_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting schedule for {}", user_id);
+            let schedule_request =
+                get_channel_stream_schedule::GetChannelStreamScheduleRequest::builder()
+                    .broadcaster_id(user_id.clone())
+                    .first(25)
+                    .build();
+
+            // channels that never set up a schedule are a 404
+            let response = client.req_get(schedule_request, token).await;
+            let segments = match response {
+                Ok(r) => r.data.segments,
+                Err(ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                    status,
+                    ..
+                })) if status == reqwest::StatusCode::NOT_FOUND => Vec::new(),
+                Err(e) => return Err(handle_helix_error(e)),
+            };
+
+            Ok(segments.into_iter().map(ScheduledSegment::from).collect())
+        })
+        .await
+}
+
+/// The team called `name` with its members.
+async fn get_team(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    name: String,
+) -> Result<Team, TwitchRssError> {
+    let key = cache::team_key(&name);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting team {}", name);
+            let team_request = get_teams::GetTeamsRequest::builder()
+                .name(Some(name.clone()))
+                .build();
+
+            // like videos, unknown teams are a 404 rather than an empty list
+            let response = client.req_get(team_request, token).await;
+            match response {
+                Ok(r) => r.data.into_iter().next(),
+                Err(ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                    status,
+                    ..
+                })) if status == reqwest::StatusCode::NOT_FOUND => None,
+                Err(e) => return Err(handle_helix_error(e)),
+            }
+            .ok_or_else(|| TwitchRssError::UnknownTeam(name.clone()))
+        })
+        .await
+}
+
+async fn get_video(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    video_id: VideoId,
+) -> Result<Video, TwitchRssError> {
+    let key = cache::video_key(&video_id);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting video {}", video_id);
+            let video_request = get_videos::GetVideosRequest::builder()
+                .id(vec![video_id.clone()])
+                .build();
+
+            // unknown ids are reported as a 404 by Helix rather than an empty list
+            let response = client.req_get(video_request, token).await;
+            match response {
+                Ok(r) => r.data.into_iter().next(),
+                Err(ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+                    status,
+                    ..
+                })) if status == reqwest::StatusCode::NOT_FOUND => None,
+                Err(e) => return Err(handle_helix_error(e)),
+            }
+            .ok_or_else(|| TwitchRssError::UnknownVideo(video_id.to_string()))
+        })
+        .await
+}
+
+/// Top clips of `window`.
+async fn get_user_clips(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    user_id: UserId,
+    window: ClipWindow,
+) -> Result<Vec<Clip>, TwitchRssError> {
+    let generation = cache::clips_generation(cache, &user_id).await;
+    let key = cache::clips_key(&user_id, generation, &window.key());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting clips for {}", user_id);
+            let (started_at, ended_at) = window.bounds();
+
+            let clip_request = get_clips::GetClipsRequest::builder()
+                .broadcaster_id(user_id.clone())
+                .started_at(started_at.and_then(|t| t.try_into().ok()))
+                .ended_at(ended_at.and_then(|t| t.try_into().ok()))
+                .first(20)
+                .build();
+
+            let clips = client
+                .req_get(clip_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(clips)
+        })
+        .await
+}
+
+async fn get_game_clips(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    game_id: CategoryId,
+    window: ClipWindow,
+) -> Result<Vec<Clip>, TwitchRssError> {
+    let key = cache::game_clips_key(&game_id, &window.key());
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting clips for game {}", game_id);
+            let (started_at, ended_at) = window.bounds();
+
+            let clip_request = get_clips::GetClipsRequest::builder()
+                .game_id(game_id.clone())
+                .started_at(started_at.and_then(|t| t.try_into().ok()))
+                .ended_at(ended_at.and_then(|t| t.try_into().ok()))
+                .first(20)
+                .build();
+
+            let clips = client
+                .req_get(clip_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(clips)
+        })
+        .await
+}
+
+/// Streams of those of `logins` that are currently live.
+async fn get_live_streams(
+    cache: &Cache,
+    ttl: Duration,
+    client: &HelixClient<'static, ReqwestClient>,
+    token: &AppAccessToken,
+    logins: &[String],
+) -> Result<Vec<Stream>, TwitchRssError> {
+    let key = cache::streams_key(logins);
+    cache
+        .get_or_try_insert(&key, ttl, || async {
+            println!("getting streams for {}", logins.join(","));
+            let stream_request = get_streams::GetStreamsRequest::builder()
+                .user_login(logins.iter().map(|l| l.as_str().into()).collect())
+                .first(logins.len())
+                .build();
+
+            let streams = client
+                .req_get(stream_request, token)
+                .await
+                .map_err(handle_helix_error)?
+                .data;
+
+            Ok(streams)
+        })
+        .await
+}
@@ -0,0 +1,199 @@
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use twitch_api2::helix::streams::Stream;
+use twitch_api2::helix::HelixClient;
+
+use crate::feed::{Feed, FeedItem, FeedNaming};
+use crate::i18n::Locale;
+use crate::options::{self, LocaleQuery};
+use crate::render::Rendered;
+use crate::{category, get_live_streams, get_token, login, render_feed, AppState, TwitchRssError};
+
+/// Helix accepts at most this many logins per request
+pub const MAX_NAMES: usize = 100;
+
+#[derive(Deserialize)]
+pub struct LiveQuery {
+    /// Comma separated logins
+    names: String,
+    /// Language of generated text, one of [`LOCALES`](crate::i18n::LOCALES)
+    locale: Option<String>,
+}
+
+/// A live channel in `/live.json`.
+#[derive(Serialize)]
+pub struct LiveChannel {
+    pub login: String,
+    pub display_name: String,
+    pub title: String,
+    pub game: String,
+    pub viewers: usize,
+    /// RFC 3339 timestamp
+    pub started_at: String,
+    pub url: String,
+    pub thumbnail: String,
+}
+
+/// `/live?names=a,b,c`, an item for every listed channel that is currently live.
+pub async fn feed(
+    Query(query): Query<LiveQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let logins = login::parse_logins(&query.names, MAX_NAMES)?;
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+    let streams = live_streams(&state, &logins).await?;
+
+    let feed = Feed {
+        title: locale.live_channels_title().to_string(),
+        link: "https://www.twitch.tv/directory/following".to_string(),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.live_ttl),
+        items: streams.iter().map(|s| stream_to_item(s, locale)).collect(),
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `/live.json?names=a,b,c`, the same as [`feed`] for scripts.
+pub async fn json(
+    Query(query): Query<LiveQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<LiveChannel>>, TwitchRssError> {
+    let logins = login::parse_logins(&query.names, MAX_NAMES)?;
+    let streams = live_streams(&state, &logins).await?;
+
+    let channels = streams
+        .iter()
+        .map(|s| LiveChannel {
+            login: s.user_login.to_string(),
+            display_name: s.user_name.to_string(),
+            title: s.title.clone(),
+            game: s.game_name.clone(),
+            viewers: s.viewer_count,
+            started_at: s.started_at.to_utc().format(&Rfc3339).unwrap_or_default(),
+            url: stream_url(s),
+            thumbnail: thumbnail_url(s),
+        })
+        .collect();
+
+    Ok(Json(channels))
+}
+
+/// `/channel/{name}/live`, a single item while the channel is live and none otherwise.
+pub async fn channel(
+    Path(name): Path<String>,
+    Query(query): Query<LocaleQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+    let streams = live_streams(&state, std::slice::from_ref(&name)).await?;
+
+    let mut feed = Feed {
+        title: locale.live_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.live_ttl),
+        items: streams.iter().map(|s| stream_to_item(s, locale)).collect(),
+    };
+    feed.apply_templates(&state.config.channel, &FeedNaming::twitch(&name, "live"));
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// An item for the stream `login` is running, to pin above its videos. The id is derived from the
+/// stream, so readers show it once per broadcast.
+pub async fn pinned_item(
+    state: &AppState,
+    login: &str,
+    locale: Locale,
+) -> Result<Option<FeedItem>, TwitchRssError> {
+    let streams = live_streams(state, &[login.to_string()]).await?;
+
+    Ok(streams.first().map(|stream| FeedItem {
+        id: format!("live-{}", stream.id),
+        title: locale.live_marker(&stream.title),
+        ..stream_to_item(stream, locale)
+    }))
+}
+
+/// Running streams of `logins`, their categories are remembered for the videos recorded of them.
+pub async fn live_streams(
+    state: &AppState,
+    logins: &[String],
+) -> Result<Vec<Stream>, TwitchRssError> {
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let streams = get_live_streams(
+        &state.cache,
+        state.config.live_ttl,
+        &helix_client,
+        &token,
+        logins,
+    )
+    .await?;
+
+    for stream in &streams {
+        category::remember(state, stream).await;
+    }
+
+    Ok(streams)
+}
+
+fn stream_url(stream: &Stream) -> String {
+    format!("https://www.twitch.tv/{}", stream.user_login)
+}
+
+fn thumbnail_url(stream: &Stream) -> String {
+    stream
+        .thumbnail_url
+        .replace("{width}", "512")
+        .replace("{height}", "288")
+}
+
+pub fn stream_to_item(stream: &Stream, locale: Locale) -> FeedItem {
+    let url = stream_url(stream);
+    let description = format!(
+        "<a href=\"{}\"><img src=\"{}\" /></a><br />{}<br />{}",
+        url,
+        thumbnail_url(stream),
+        locale.playing(&stream.game_name, stream.viewer_count),
+        stream.title
+    );
+
+    FeedItem {
+        // a new stream gets a new id, so each broadcast shows up as a new item
+        id: stream.id.to_string(),
+        title: locale.is_live(stream.user_name.as_str(), &stream.title),
+        link: url,
+        description,
+        published: stream.started_at.to_utc(),
+        sub_only: false,
+        duration: None,
+        thumbnail: Some(thumbnail_url(stream)),
+        views: None,
+        category: Some(stream.game_name.clone()).filter(|g| !g.is_empty()),
+        enclosure: None,
+        tags: Vec::new(),
+        channel: Some(stream.user_login.to_string()),
+    }
+}
@@ -0,0 +1,190 @@
+use axum::extract::{OriginalUri, Request};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::TwitchRssError;
+
+const MAX_LOGIN_LEN: usize = 25;
+
+/// Turn the (already percent-decoded) `:name` path segment into something that can be a Twitch
+/// login, lowercased, or explain why it can't be.
+///
+/// Input is NFKC normalized first so visually identical forms (e.g. fullwidth letters pasted from
+/// elsewhere) resolve to the same login instead of failing inconsistently.
+pub fn parse_login(raw: &str) -> Result<String, TwitchRssError> {
+    let login = raw.trim().nfkc().collect::<String>();
+
+    if login.is_empty() {
+        return Err(invalid(raw, "channel name is empty"));
+    }
+
+    if let Some(c) = login.chars().find(|c| !is_login_char(*c)) {
+        let reason = if c == '%' {
+            "contains '%', the name may have been percent-encoded twice".to_string()
+        } else if c == '.'
+            && login
+                .rsplit_once('.')
+                .is_some_and(|(_, ext)| !ext.is_empty())
+        {
+            // probes like `favicon.ico` and `wp-login.php` shouldn't cost a Helix request
+            "looks like a file name, not a channel".to_string()
+        } else {
+            format!(
+                "contains {:?}, only ASCII letters, digits and '_' are allowed",
+                c
+            )
+        };
+        return Err(invalid(raw, &reason));
+    }
+
+    if login.len() > MAX_LOGIN_LEN {
+        return Err(invalid(
+            raw,
+            &format!("longer than {} characters", MAX_LOGIN_LEN),
+        ));
+    }
+
+    // Twitch logins are lowercase, other spellings would be looked up and cached separately
+    Ok(login.to_ascii_lowercase())
+}
+
+/// Longest user id accepted, Twitch's are far from it.
+const MAX_USER_ID_LEN: usize = 20;
+
+/// Check that the `:user_id` path segment is a numeric Twitch user id, as `/api/channel/{name}`
+/// returns them.
+pub fn parse_user_id(raw: &str) -> Result<String, TwitchRssError> {
+    let user_id = raw.trim();
+    if user_id.is_empty() || !user_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid(raw, "is not a numeric user id"));
+    }
+    if user_id.len() > MAX_USER_ID_LEN {
+        return Err(invalid(
+            raw,
+            &format!("longer than {} digits", MAX_USER_ID_LEN),
+        ));
+    }
+
+    Ok(user_id.to_string())
+}
+
+/// Redirect requests for a channel spelled with capitals, like `/channel/SomeName/vod`, to the
+/// lowercase URL, so readers subscribe to a single URL per channel.
+pub async fn canonical_redirect(request: Request, next: Next) -> Response {
+    // nested routers see the path without their prefix, the first segment is the login
+    let path = request.uri().path();
+    let segment = path.trim_start_matches('/').split('/').next().unwrap_or("");
+    let capitalized =
+        segment.chars().any(|c| c.is_ascii_uppercase()) && segment.chars().all(is_login_char);
+    if !capitalized {
+        return next.run(request).await;
+    }
+
+    let original = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri,
+        None => request.uri(),
+    };
+    let prefix = &original.path()[..original.path().len() - path.len()];
+    let rest = &path[1 + segment.len()..];
+    let mut location = format!("{}/{}{}", prefix, segment.to_ascii_lowercase(), rest);
+    if let Some(query) = original.query() {
+        location.push('?');
+        location.push_str(query);
+    }
+
+    // 301 rather than 308, feed readers update subscriptions on it
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
+
+fn is_login_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Parse a comma separated `names` query parameter into at most `max` distinct logins.
+pub fn parse_logins(names: &str, max: usize) -> Result<Vec<String>, TwitchRssError> {
+    let mut logins = names
+        .split(',')
+        .filter(|n| !n.trim().is_empty())
+        .map(parse_login)
+        .collect::<Result<Vec<_>, _>>()?;
+    logins.sort();
+    logins.dedup();
+
+    if logins.is_empty() {
+        return Err(TwitchRssError::InvalidQuery(
+            "names must list at least one channel".to_string(),
+        ));
+    }
+    if logins.len() > max {
+        return Err(TwitchRssError::InvalidQuery(format!(
+            "names lists {} channels, at most {} are supported",
+            logins.len(),
+            max
+        )));
+    }
+
+    Ok(logins)
+}
+
+/// The 404 for `login`, pointing at `suggestions` when Twitch knows similarly named channels.
+pub fn unknown(login: &str, suggestions: &[String]) -> TwitchRssError {
+    match suggestions {
+        [] => TwitchRssError::UnknownChannel(login.to_string()),
+        _ => TwitchRssError::UnknownChannel(format!(
+            "{}, did you mean {}?",
+            login,
+            suggestions.join(", ")
+        )),
+    }
+}
+
+fn invalid(raw: &str, reason: &str) -> TwitchRssError {
+    TwitchRssError::InvalidChannel(format!("{:?} {}", raw, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(raw: &str) -> String {
+        match parse_login(raw) {
+            Err(TwitchRssError::InvalidChannel(reason)) => reason,
+            _ => panic!("{:?} was not rejected as a channel name", raw),
+        }
+    }
+
+    #[test]
+    fn normalizes_logins() {
+        assert_eq!(
+            parse_login(" SomeName_1 ").ok().as_deref(),
+            Some("somename_1")
+        );
+        // fullwidth letters
+        assert_eq!(parse_login("\u{ff26}oo").ok().as_deref(), Some("foo"));
+    }
+
+    #[test]
+    fn rejects_what_cant_be_a_login() {
+        assert!(reason("").contains("empty"));
+        assert!(reason("favicon.ico").contains("file name"));
+        assert!(reason("some%2520one").contains("percent-encoded twice"));
+        assert!(reason("some one").contains("only ASCII letters"));
+        assert!(reason(&"a".repeat(26)).contains("longer than 25"));
+        assert!(parse_login(&"a".repeat(25)).is_ok());
+    }
+
+    #[test]
+    fn suggests_near_matches() {
+        assert_eq!(unknown("fooo", &[]).to_string(), "UnknownChannel(fooo)");
+        assert_eq!(
+            unknown("fooo", &["foo".to_string(), "foo_o".to_string()]).to_string(),
+            "UnknownChannel(fooo, did you mean foo, foo_o?)"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+
+use crate::cache::{self, Cache};
+use crate::config::MediaConfig;
+use crate::feed::{Enclosure, FeedItem};
+
+/// Most resolvers running at once, across all requests.
+const MAX_RUNNING: usize = 4;
+
+/// How long a video that couldn't be resolved is left alone.
+const FAILURE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Finds a playable media URL for a video or clip page.
+#[async_trait]
+pub trait MediaResolver: Send + Sync {
+    /// Media URL of the video at `link`, `None` if there is none.
+    async fn resolve(&self, link: &str) -> Result<Option<String>, String>;
+}
+
+/// Runs a command like `yt-dlp -g` with the page URL as its last argument, and takes the first
+/// URL it prints.
+pub struct CommandResolver {
+    command: Vec<String>,
+}
+
+#[async_trait]
+impl MediaResolver for CommandResolver {
+    async fn resolve(&self, link: &str) -> Result<Option<String>, String> {
+        let (program, args) = self.command.split_first().ok_or("empty command")?;
+
+        // a command that outlives its timeout is killed when its future is dropped
+        let output = Command::new(program)
+            .args(args)
+            .arg(link)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !output.status.success() {
+            return Err(format!("exited with {}", output.status));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("https://") || line.starts_with("http://"))
+            .map(str::to_string))
+    }
+}
+
+/// Attaches resolved media to feed items as enclosures.
+///
+/// Resolving is slow, so results are cached per video and requests only wait for them up to the
+/// timeout. Resolvers still running by then finish in the background for the next request.
+pub struct Media {
+    resolver: Arc<dyn MediaResolver>,
+    running: Arc<Semaphore>,
+    /// Links being resolved, so concurrent requests don't resolve the same video twice
+    in_flight: Arc<Mutex<HashSet<String>>>,
+    timeout: Duration,
+    ttl: Duration,
+}
+
+impl Media {
+    pub fn new(config: &MediaConfig) -> Self {
+        Self {
+            resolver: Arc::new(CommandResolver {
+                command: config.command.clone(),
+            }),
+            running: Arc::new(Semaphore::new(MAX_RUNNING)),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            timeout: config.timeout,
+            ttl: config.ttl,
+        }
+    }
+
+    /// Add enclosures to `items` that can be watched by everyone.
+    pub async fn attach(&self, cache: &Cache, items: &mut [FeedItem]) {
+        let deadline = Instant::now() + self.timeout;
+
+        let mut pending = Vec::new();
+        for (i, item) in items.iter_mut().enumerate() {
+            if item.sub_only || item.enclosure.is_some() {
+                continue;
+            }
+            match cache
+                .get::<Option<String>>(&cache::media_key(&item.link))
+                .await
+            {
+                Some(url) => item.enclosure = url.map(enclosure),
+                None => {
+                    if let Some(handle) = self.spawn(cache, &item.link) {
+                        pending.push((i, handle));
+                    }
+                }
+            }
+        }
+
+        for (i, handle) in pending {
+            if let Ok(Ok(Some(url))) = tokio::time::timeout_at(deadline, handle).await {
+                items[i].enclosure = Some(enclosure(url));
+            }
+        }
+    }
+
+    /// Resolve `link` in the background, unless that is already happening.
+    fn spawn(&self, cache: &Cache, link: &str) -> Option<tokio::task::JoinHandle<Option<String>>> {
+        if !self
+            .in_flight
+            .lock()
+            .expect("not poisoned")
+            .insert(link.to_string())
+        {
+            return None;
+        }
+
+        let resolver = self.resolver.clone();
+        let running = self.running.clone();
+        let in_flight = self.in_flight.clone();
+        let (cache, link, timeout, ttl) = (cache.clone(), link.to_string(), self.timeout, self.ttl);
+
+        Some(tokio::spawn(async move {
+            let result = match running.acquire_owned().await {
+                Ok(_permit) => {
+                    println!("resolving media for {}", link);
+                    tokio::time::timeout(timeout, resolver.resolve(&link))
+                        .await
+                        .unwrap_or_else(|_| Err("timed out".to_string()))
+                }
+                Err(e) => Err(e.to_string()),
+            };
+
+            let url = match result {
+                Ok(url) => {
+                    cache.set(&cache::media_key(&link), &url, ttl).await;
+                    url
+                }
+                Err(e) => {
+                    println!("resolving media for {} failed: {}", link, e);
+                    cache
+                        .set(
+                            &cache::media_key(&link),
+                            &None::<String>,
+                            FAILURE_TTL.min(ttl),
+                        )
+                        .await;
+                    None
+                }
+            };
+
+            in_flight.lock().expect("not poisoned").remove(&link);
+            url
+        }))
+    }
+}
+
+/// HLS playlists are what Twitch serves, anything else is taken to be an MP4 download.
+fn enclosure(url: String) -> Enclosure {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let mime_type = match path.ends_with(".m3u8") {
+        true => "application/vnd.apple.mpegurl",
+        false => "video/mp4",
+    };
+
+    Enclosure {
+        url,
+        mime_type: mime_type.to_string(),
+    }
+}
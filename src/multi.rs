@@ -0,0 +1,138 @@
+use axum::extract::{FromRequest, OriginalUri, Path, Query, Request, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::{Form, Json};
+use futures::future::try_join_all;
+use serde::Deserialize;
+
+use crate::feed::{Feed, FeedItem};
+use crate::options::{FeedOptions, FeedQuery};
+use crate::provider::{self, VideoProvider};
+use crate::render::Rendered;
+use crate::{login, render_feed, AppState, TwitchRssError};
+
+/// Every channel costs a few Helix requests, same limit as the merged clips feed
+pub const MAX_NAMES: usize = 25;
+
+/// Lists too long for a path, still bounded so a single request can't exhaust the Helix rate limit
+pub const MAX_BATCH_NAMES: usize = 100;
+
+/// Body of a batch request, as JSON or as a form.
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    names: Names,
+    /// Same as the `format` query parameter of the VOD feed
+    format: Option<String>,
+    #[serde(flatten)]
+    options: FeedQuery,
+}
+
+/// Channels as a JSON array or as a comma separated string, which is all a form can send.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Names {
+    List(Vec<String>),
+    Joined(String),
+}
+
+/// `/multi/{names}/vod`, the VOD feeds of several comma separated channels merged into one.
+pub async fn vod(
+    Path(names): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let logins = login::parse_logins(&names, MAX_NAMES)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let feed = merged_feed(&state, &logins, &options).await?;
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `POST /multi/vod`, the same merged feed for channel lists that don't fit in a path.
+pub async fn batch(
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<impl IntoResponse, TwitchRssError> {
+    let accept = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = parse_batch(request).await?;
+
+    let names = match body.names {
+        Names::List(names) => names.join(","),
+        Names::Joined(names) => names,
+    };
+    let logins = login::parse_logins(&names, MAX_BATCH_NAMES)?;
+    let options = FeedOptions::try_from(body.options)?;
+    let renderer = state
+        .renderers
+        .negotiate(body.format.as_deref(), accept.as_deref())?;
+
+    let feed = merged_feed(&state, &logins, &options).await?;
+
+    Ok((
+        [(header::VARY, "Accept")],
+        Rendered::new(renderer.as_ref(), &feed),
+    ))
+}
+
+/// JSON when the request says so, a form otherwise.
+async fn parse_batch(request: Request) -> Result<BatchRequest, TwitchRssError> {
+    let json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|t| t.starts_with("application/json"));
+
+    if json {
+        Json::from_request(request, &())
+            .await
+            .map(|Json(body)| body)
+            .map_err(|e| TwitchRssError::InvalidQuery(e.body_text()))
+    } else {
+        Form::from_request(request, &())
+            .await
+            .map(|Form(body)| body)
+            .map_err(|e| TwitchRssError::InvalidQuery(e.body_text()))
+    }
+}
+
+/// Videos of every one of `logins` interleaved by date, titles prefixed with the channel.
+pub async fn merged_feed(
+    state: &AppState,
+    logins: &[String],
+    options: &FeedOptions,
+) -> Result<Feed, TwitchRssError> {
+    let items = try_join_all(logins.iter().map(|login| async move {
+        let items = provider::Twitch.videos(state, login, options).await?;
+
+        Ok::<_, TwitchRssError>(items.into_iter().map(move |item| FeedItem {
+            title: format!("{}: {}", login, item.title),
+            ..item
+        }))
+    }))
+    .await?
+    .into_iter()
+    .flatten()
+    .collect();
+
+    Ok(Feed {
+        title: options
+            .items
+            .locale
+            .unwrap_or(state.config.locale)
+            .vods_title(&logins.join(", "), "Twitch"),
+        link: "https://www.twitch.tv/directory/following".to_string(),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.videos_ttl),
+        items: provider::finish_items(state, options, items).await,
+    })
+}
@@ -0,0 +1,51 @@
+use serde::Serialize;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::types::VideoType;
+
+use crate::AppState;
+
+/// What is posted to the `notify` URLs of a tracked channel for each new video.
+#[derive(Serialize)]
+struct NewVideo<'a> {
+    channel: &'a str,
+    id: &'a str,
+    title: &'a str,
+    url: &'a str,
+    created_at: &'a str,
+    #[serde(rename = "type")]
+    video_type: &'a VideoType,
+}
+
+/// Post the videos of `login` that are new since the last call to each of `targets`.
+///
+/// Failed posts are logged and not retried, the next video is announced either way.
+pub async fn announce(state: &AppState, login: &str, targets: &[String], videos: &[Video]) {
+    let new = state.tracked.new_videos(login, videos).await;
+    if targets.is_empty() {
+        return;
+    }
+
+    // oldest first, in the order they were published
+    for video in new.into_iter().rev() {
+        let body = NewVideo {
+            channel: login,
+            id: video.id.as_str(),
+            title: &video.title,
+            url: &video.url,
+            created_at: video.created_at.as_str(),
+            video_type: &video.type_,
+        };
+        for target in targets {
+            let response = state
+                .client
+                .post(target)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            if let Err(e) = response {
+                println!("notifying {} about video {}: {}", target, video.id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use axum::extract::{OriginalUri, State};
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+
+use crate::preview::escape;
+use crate::AppState;
+
+/// `/opml`, the VOD feeds of every tracked channel as an OPML file, to subscribe to all of them at
+/// once in a reader.
+///
+/// Feed URLs start with `BASE_URL` if it is set, otherwise with the `Host` the request was sent to.
+pub async fn export(
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    // servers nested under a prefix serve this below it too
+    let prefix = uri.path().strip_suffix("/opml").unwrap_or_default();
+    let base = state
+        .config
+        .public_url(prefix)
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|h| h.to_str().ok())
+                .unwrap_or("localhost");
+            format!("http://{}{}", host, prefix)
+        });
+    let logins = state.tracked.list().await.into_keys().collect::<Vec<_>>();
+
+    (
+        [(header::CONTENT_TYPE, "text/x-opml")],
+        render(&base, &logins),
+    )
+}
+
+fn render(base: &str, logins: &[String]) -> String {
+    let outlines = logins
+        .iter()
+        .map(|login| {
+            format!(
+                "    <outline type=\"rss\" text=\"{login}\" title=\"{login}\" xmlUrl=\"{feed}\" \
+                 htmlUrl=\"https://www.twitch.tv/{login}\"/>\n",
+                login = escape(login),
+                feed = escape(&format!("{}/channel/{}/vod", base, login)),
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<opml version=\"2.0\">
+  <head>
+    <title>Twitch VODs</title>
+  </head>
+  <body>
+{}  </body>
+</opml>
+",
+        outlines
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_a_feed_per_channel() {
+        let opml = render(
+            "https://feeds.example.com/a&b",
+            &["bar".to_string(), "foo".to_string()],
+        );
+        assert!(opml.contains(
+            "xmlUrl=\"https://feeds.example.com/a&amp;b/channel/bar/vod\" \
+             htmlUrl=\"https://www.twitch.tv/bar\""
+        ));
+        assert_eq!(opml.matches("<outline ").count(), 2);
+    }
+}
@@ -0,0 +1,932 @@
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use time::format_description::well_known::Rfc3339;
+use time::{Date, Duration, OffsetDateTime};
+use twitch_api2::helix::videos::{Sort, Video, VideoPeriod, VideoTypeFilter};
+use twitch_api2::types::{VideoPrivacy, VideoType};
+
+use crate::duration;
+use crate::feed::{self, FeedItem, PubDate, ThumbnailSize};
+use crate::i18n::{Locale, LOCALES};
+use crate::timezone::TimeZone;
+use crate::TwitchRssError;
+
+/// Query parameters accepted by the VOD feeds.
+#[derive(Debug, Default, Deserialize)]
+pub struct FeedQuery {
+    /// RFC 3339 timestamp or date, only videos published from then on are included
+    since: Option<String>,
+    /// RFC 3339 timestamp or date, only videos published before it are included
+    until: Option<String>,
+    /// `0` leaves out subscriber-only videos
+    subonly: Option<String>,
+    /// `1` pins an item for the running stream above the videos
+    live: Option<String>,
+    /// `archive`, `highlight`, `upload` or `all`
+    #[serde(rename = "type")]
+    video_type: Option<String>,
+    /// Number of videos, at most [`MAX_LIMIT`]
+    limit: Option<String>,
+    /// Number of pages of videos to fetch
+    pages: Option<String>,
+    /// `1` fetches as many pages as allowed
+    all: Option<String>,
+    /// `day`, `week`, `month` or `all`
+    period: Option<String>,
+    /// `time`, `trending` or `views`
+    sort: Option<String>,
+    /// Comma separated language codes, e.g. `en,de`
+    lang: Option<String>,
+    /// Shortest video to include, e.g. `30m`
+    min_duration: Option<String>,
+    /// Oldest item to include, e.g. `14d`
+    max_age: Option<String>,
+    /// Regular expression titles have to match
+    title: Option<String>,
+    /// Regular expression titles must not match
+    exclude_title: Option<String>,
+    /// `1` collapses reruns into the broadcast they repeat
+    dedupe: Option<String>,
+    /// `1` leaves out videos that can't be watched by everyone yet
+    public: Option<String>,
+    /// `1` appends the length of items to their titles
+    title_duration: Option<String>,
+    /// `created_at` or `published_at`, which timestamp videos are dated with
+    pub_date: Option<String>,
+    /// Thumbnail size like `320x180`, one of [`THUMBNAIL_SIZES`]
+    thumb: Option<String>,
+    /// `1` leaves thumbnails out of items
+    no_thumb: Option<String>,
+    /// `1` embeds the Twitch player in descriptions
+    embed: Option<String>,
+    /// Language of generated text, one of [`LOCALES`]
+    locale: Option<String>,
+    /// Time zone of dates in descriptions, like `Europe/Berlin`
+    tz: Option<String>,
+}
+
+/// Output format of a feed, taking precedence over the `Accept` header.
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    /// Name of a registered renderer, e.g. `atom`
+    pub format: Option<String>,
+}
+
+/// Language of the generated text of feeds that take no other options.
+#[derive(Debug, Default, Deserialize)]
+pub struct LocaleQuery {
+    /// One of [`LOCALES`]
+    pub locale: Option<String>,
+}
+
+/// Validated form of [`FeedQuery`].
+#[derive(Debug)]
+pub struct FeedOptions {
+    pub since: Option<OffsetDateTime>,
+    pub until: Option<OffsetDateTime>,
+    pub sub_only: bool,
+    /// Only used by the VOD feed of a single channel
+    pub live: bool,
+    /// What to ask Helix for, only used by Twitch channel feeds
+    pub videos: VideoFilter,
+    /// In seconds, items of unknown length are kept
+    pub min_duration: Option<u64>,
+    /// How long ago items may have been published
+    pub max_age: Option<Duration>,
+    pub title: Option<Regex>,
+    pub exclude_title: Option<Regex>,
+    pub dedupe: bool,
+    /// Append lengths to titles, `None` leaves it to the configuration
+    pub title_duration: Option<bool>,
+    /// Time zone of dates in descriptions, `None` leaves it to the configuration
+    pub time_zone: Option<TimeZone>,
+    /// How Twitch videos are turned into items
+    pub items: ItemOptions,
+}
+
+/// How Helix videos are turned into feed items.
+#[derive(Clone, Debug, Default)]
+pub struct ItemOptions {
+    /// Timestamp videos are dated with, `None` leaves it to the configuration
+    pub pub_date: Option<PubDate>,
+    /// `None` leaves it to the channel's override
+    pub thumbnail_size: Option<ThumbnailSize>,
+    /// Leave thumbnails out, `None` leaves it to the configuration
+    pub no_thumbnails: Option<bool>,
+    /// Embed the player on the configured sites
+    pub embed: bool,
+    /// Language of generated text, `None` leaves it to the configuration
+    pub locale: Option<Locale>,
+}
+
+/// Values of `type`, in the order Helix documents them.
+pub const VIDEO_TYPES: [&str; 4] = ["all", "upload", "archive", "highlight"];
+
+/// Values of `thumb`, the sizes Twitch renders video thumbnails in without scaling artifacts.
+pub const THUMBNAIL_SIZES: [&str; 7] = [
+    "320x180",
+    "480x270",
+    "512x288",
+    "640x360",
+    "960x540",
+    "1280x720",
+    "1920x1080",
+];
+
+/// Most videos Helix returns at once.
+pub const MAX_LIMIT: usize = 100;
+
+/// Most languages in `lang`.
+const MAX_LANGUAGES: usize = 10;
+
+/// Longest `title` and `exclude_title` patterns.
+const MAX_PATTERN_LEN: usize = 200;
+
+/// Most memory a compiled title pattern may take. Matching is linear in the title either way, this
+/// keeps patterns like `(a{1000}){1000}` from being compiled at all.
+const MAX_PATTERN_SIZE: usize = 64 * 1024;
+
+/// Which videos of a channel Helix is asked for.
+#[derive(Clone, Debug, Default)]
+pub struct VideoFilter {
+    /// `None` when `type` wasn't given, which other settings may fill in
+    pub video_type: Option<VideoTypeFilter>,
+    /// Helix `first`, which defaults to 20
+    pub first: Option<usize>,
+    /// Follow the pagination cursor for this many full pages, within the configured maximum
+    pub pages: Option<usize>,
+    /// Helix `period`, how far back videos were created
+    pub period: Option<VideoPeriod>,
+    /// Helix `sort`, also the order of the feed
+    pub sort: Option<Sort>,
+    /// Languages videos may be in, any if empty
+    pub languages: Vec<String>,
+    /// Only public videos that finished processing, `None` leaves it to the configuration
+    pub public_only: Option<bool>,
+}
+
+impl VideoFilter {
+    /// Distinguishes the cached lists of the same channel.
+    pub fn key(&self) -> String {
+        let mut key = self.type_key().to_string();
+        match (self.pages, self.first) {
+            (Some(pages), _) => key.push_str(&format!(":pages={}", pages)),
+            (None, Some(first)) => key.push_str(&format!(":first={}", first)),
+            (None, None) => {}
+        }
+        if let Some(period) = self.period_key() {
+            key.push_str(&format!(":period={}", period));
+        }
+        match self.sort {
+            None | Some(Sort::Time) => {}
+            Some(Sort::Trending) => key.push_str(":sort=trending"),
+            Some(Sort::Views) => key.push_str(":sort=views"),
+        }
+        if let Some(language) = self.helix_language() {
+            key.push_str(&format!(":lang={}", language));
+        }
+        key
+    }
+
+    /// Helix only filters by a single language, more are filtered once the videos are fetched.
+    pub fn helix_language(&self) -> Option<&String> {
+        match self.languages.as_slice() {
+            [language] => Some(language),
+            _ => None,
+        }
+    }
+
+    /// The same filter with no more than `max_pages` pages.
+    pub fn capped(&self, max_pages: usize) -> Self {
+        Self {
+            pages: self.pages.map(|pages| pages.min(max_pages).max(1)),
+            ..self.clone()
+        }
+    }
+
+    fn period_key(&self) -> Option<&'static str> {
+        match self.period {
+            None | Some(VideoPeriod::All) => None,
+            Some(VideoPeriod::Day) => Some("day"),
+            Some(VideoPeriod::Week) => Some("week"),
+            Some(VideoPeriod::Month) => Some("month"),
+        }
+    }
+
+    fn type_key(&self) -> &'static str {
+        match self.video_type {
+            None | Some(VideoTypeFilter::All) => "all",
+            Some(VideoTypeFilter::Upload) => "upload",
+            Some(VideoTypeFilter::Archive) => "archive",
+            Some(VideoTypeFilter::Highlight) => "highlight",
+        }
+    }
+
+    /// Whether `video` would have been returned by Helix, for lists that weren't fetched with
+    /// (all of) this filter.
+    pub fn matches(&self, video: &Video) -> bool {
+        let type_matches = match self.video_type {
+            None | Some(VideoTypeFilter::All) => true,
+            Some(VideoTypeFilter::Upload) => video.type_ == VideoType::Upload,
+            Some(VideoTypeFilter::Archive) => video.type_ == VideoType::Archive,
+            Some(VideoTypeFilter::Highlight) => video.type_ == VideoType::Highlight,
+        };
+
+        let max_age = match self.period {
+            None | Some(VideoPeriod::All) => None,
+            Some(VideoPeriod::Day) => Some(Duration::days(1)),
+            Some(VideoPeriod::Week) => Some(Duration::weeks(1)),
+            Some(VideoPeriod::Month) => Some(Duration::days(30)),
+        };
+        let period_matches =
+            max_age.is_none_or(|age| video.created_at.to_utc() > OffsetDateTime::now_utc() - age);
+
+        let language_matches =
+            self.languages.is_empty() || self.languages.contains(&video.language);
+
+        let public_matches = !self.public_only.unwrap_or(false)
+            || (video.viewable == VideoPrivacy::Public && !feed::is_processing(video));
+
+        type_matches && period_matches && language_matches && public_matches
+    }
+}
+
+fn video_type(value: Option<&str>) -> Result<Option<VideoTypeFilter>, TwitchRssError> {
+    let video_type = match value {
+        None => return Ok(None),
+        Some("all") => VideoTypeFilter::All,
+        Some("upload") => VideoTypeFilter::Upload,
+        Some("archive") => VideoTypeFilter::Archive,
+        Some("highlight") => VideoTypeFilter::Highlight,
+        Some(other) => {
+            return Err(TwitchRssError::InvalidQuery(format!(
+                "type={:?} is not one of {}",
+                other,
+                VIDEO_TYPES.join(", ")
+            )))
+        }
+    };
+
+    Ok(Some(video_type))
+}
+
+fn period(value: Option<&str>) -> Result<Option<VideoPeriod>, TwitchRssError> {
+    let period = match value {
+        None => return Ok(None),
+        Some("all") => VideoPeriod::All,
+        Some("day") => VideoPeriod::Day,
+        Some("week") => VideoPeriod::Week,
+        Some("month") => VideoPeriod::Month,
+        Some(other) => {
+            return Err(TwitchRssError::InvalidQuery(format!(
+                "period={:?} is not one of day, week, month, all",
+                other
+            )))
+        }
+    };
+
+    Ok(Some(period))
+}
+
+fn sort(value: Option<&str>) -> Result<Option<Sort>, TwitchRssError> {
+    let sort = match value {
+        None => return Ok(None),
+        Some("time") => Sort::Time,
+        Some("trending") => Sort::Trending,
+        Some("views") => Sort::Views,
+        Some(other) => {
+            return Err(TwitchRssError::InvalidQuery(format!(
+                "sort={:?} is not one of time, trending, views",
+                other
+            )))
+        }
+    };
+
+    Ok(Some(sort))
+}
+
+fn languages(value: Option<&str>) -> Result<Vec<String>, TwitchRssError> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+
+    let mut languages = Vec::new();
+    for raw in value.split(',') {
+        let language = raw.trim().to_ascii_lowercase();
+        if !is_language(&language) {
+            return Err(TwitchRssError::InvalidQuery(format!(
+                "lang={:?} is not a language code like en",
+                raw
+            )));
+        }
+        if !languages.contains(&language) {
+            languages.push(language);
+        }
+    }
+
+    if languages.len() > MAX_LANGUAGES {
+        return Err(TwitchRssError::InvalidQuery(format!(
+            "lang has more than {} languages",
+            MAX_LANGUAGES
+        )));
+    }
+
+    Ok(languages)
+}
+
+/// ISO 639-1 codes, optionally with a region as Twitch uses for e.g. `zh-hk`, or `other`.
+fn is_language(code: &str) -> bool {
+    let letters = |s: &str| s.len() == 2 && s.chars().all(|c| c.is_ascii_lowercase());
+
+    if code == "other" {
+        return true;
+    }
+
+    match code.split_once('-') {
+        Some((language, region)) => letters(language) && letters(region),
+        None => letters(code),
+    }
+}
+
+fn pages(pages: Option<&str>, all: Option<&str>) -> Result<Option<usize>, TwitchRssError> {
+    if flag("all", all, false)? {
+        // capped to the configured maximum once the videos are fetched
+        return Ok(Some(usize::MAX));
+    }
+
+    pages
+        .map(|s| match s.parse() {
+            Ok(pages) if pages >= 1 => Ok(pages),
+            _ => Err(TwitchRssError::InvalidQuery(format!(
+                "pages={:?} is not a positive number",
+                s
+            ))),
+        })
+        .transpose()
+}
+
+fn limit(value: Option<&str>) -> Result<Option<usize>, TwitchRssError> {
+    value
+        .map(|s| match s.parse() {
+            Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => Ok(limit),
+            _ => Err(TwitchRssError::InvalidQuery(format!(
+                "limit={:?} is not a number from 1 to {}",
+                s, MAX_LIMIT
+            ))),
+        })
+        .transpose()
+}
+
+/// Validates the `locale` parameter, shared by every feed that generates text.
+pub fn locale(value: Option<&str>) -> Result<Option<Locale>, TwitchRssError> {
+    value
+        .map(|s| {
+            s.parse().map_err(|_| {
+                TwitchRssError::InvalidQuery(format!(
+                    "locale={:?} is not one of {}",
+                    s,
+                    LOCALES.join(", ")
+                ))
+            })
+        })
+        .transpose()
+}
+
+impl TryFrom<FeedQuery> for FeedOptions {
+    type Error = TwitchRssError;
+
+    fn try_from(query: FeedQuery) -> Result<Self, Self::Error> {
+        let since = query.since.map(|s| timestamp("since", &s)).transpose()?;
+        let until = query.until.map(|s| timestamp("until", &s)).transpose()?;
+
+        let sub_only = flag("subonly", query.subonly.as_deref(), true)?;
+        let live = flag("live", query.live.as_deref(), false)?;
+        let dedupe = flag("dedupe", query.dedupe.as_deref(), false)?;
+        let videos = VideoFilter {
+            video_type: video_type(query.video_type.as_deref())?,
+            first: limit(query.limit.as_deref())?,
+            pages: pages(query.pages.as_deref(), query.all.as_deref())?,
+            period: period(query.period.as_deref())?,
+            sort: sort(query.sort.as_deref())?,
+            languages: languages(query.lang.as_deref())?,
+            public_only: query
+                .public
+                .map(|value| flag("public", Some(&value), false))
+                .transpose()?,
+        };
+
+        let min_duration = query
+            .min_duration
+            .map(|s| {
+                duration::parse(&s).ok_or_else(|| {
+                    TwitchRssError::InvalidQuery(format!(
+                        "min_duration={:?} is not a duration like 30m or 1h30m",
+                        s
+                    ))
+                })
+            })
+            .transpose()?;
+
+        let max_age = query
+            .max_age
+            .map(|s| match duration::parse(&s) {
+                Some(secs) if secs > 0 => {
+                    Ok(Duration::seconds(secs.try_into().unwrap_or(i64::MAX)))
+                }
+                _ => Err(TwitchRssError::InvalidQuery(format!(
+                    "max_age={:?} is not a duration like 14d or 12h",
+                    s
+                ))),
+            })
+            .transpose()?;
+
+        let title_duration = query
+            .title_duration
+            .map(|value| flag("title_duration", Some(&value), false))
+            .transpose()?;
+
+        let pub_date = query
+            .pub_date
+            .map(|s| {
+                s.parse().map_err(|_| {
+                    TwitchRssError::InvalidQuery(format!(
+                        "pub_date={:?} is not created_at or published_at",
+                        s
+                    ))
+                })
+            })
+            .transpose()?;
+
+        let thumbnail_size = query
+            .thumb
+            .map(|s| match THUMBNAIL_SIZES.contains(&s.as_str()) {
+                true => Ok(s.parse().expect("listed sizes are valid")),
+                false => Err(TwitchRssError::InvalidQuery(format!(
+                    "thumb={:?} is not one of {}",
+                    s,
+                    THUMBNAIL_SIZES.join(", ")
+                ))),
+            })
+            .transpose()?;
+
+        let no_thumbnails = query
+            .no_thumb
+            .map(|value| flag("no_thumb", Some(&value), false))
+            .transpose()?;
+
+        let embed = flag("embed", query.embed.as_deref(), false)?;
+
+        let locale = locale(query.locale.as_deref())?;
+
+        let time_zone = query
+            .tz
+            .map(|s| {
+                TimeZone::load(&s).map_err(|e| TwitchRssError::InvalidQuery(format!("tz={}", e)))
+            })
+            .transpose()?;
+
+        let title = query.title.map(|s| pattern("title", &s)).transpose()?;
+        let exclude_title = query
+            .exclude_title
+            .map(|s| pattern("exclude_title", &s))
+            .transpose()?;
+
+        Ok(Self {
+            since,
+            until,
+            sub_only,
+            live,
+            videos,
+            min_duration,
+            max_age,
+            title,
+            exclude_title,
+            dedupe,
+            title_duration,
+            time_zone,
+            items: ItemOptions {
+                pub_date,
+                thumbnail_size,
+                no_thumbnails,
+                embed,
+                locale,
+            },
+        })
+    }
+}
+
+fn pattern(name: &str, value: &str) -> Result<Regex, TwitchRssError> {
+    if value.len() > MAX_PATTERN_LEN {
+        return Err(TwitchRssError::InvalidQuery(format!(
+            "{} is longer than {} bytes",
+            name, MAX_PATTERN_LEN
+        )));
+    }
+
+    RegexBuilder::new(value)
+        .size_limit(MAX_PATTERN_SIZE)
+        .dfa_size_limit(MAX_PATTERN_SIZE)
+        .build()
+        .map_err(|e| {
+            TwitchRssError::InvalidQuery(format!(
+                "{}={:?} is not a valid regular expression: {}",
+                name, value, e
+            ))
+        })
+}
+
+/// An RFC 3339 timestamp, or a date standing for its start in UTC.
+fn timestamp(name: &str, value: &str) -> Result<OffsetDateTime, TwitchRssError> {
+    if let Ok(timestamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(timestamp);
+    }
+
+    let format = time::format_description::parse("[year]-[month]-[day]")
+        .expect("format description is valid");
+    Date::parse(value, &format)
+        .map(|date| date.midnight().assume_utc())
+        .map_err(|_| {
+            TwitchRssError::InvalidQuery(format!(
+                "{}={:?} is not an RFC 3339 timestamp or a date like 2024-06-01",
+                name, value
+            ))
+        })
+}
+
+fn flag(name: &str, value: Option<&str>, default: bool) -> Result<bool, TwitchRssError> {
+    match value {
+        None => Ok(default),
+        Some("1") | Some("true") => Ok(true),
+        Some("0") | Some("false") => Ok(false),
+        Some(other) => Err(TwitchRssError::InvalidQuery(format!(
+            "{}={:?} is not 0 or 1",
+            name, other
+        ))),
+    }
+}
+
+impl FeedOptions {
+    pub fn filter(
+        &self,
+        items: &mut Vec<FeedItem>,
+        default_time_zone: &TimeZone,
+        default_locale: Locale,
+    ) {
+        if let Some(since) = self.since {
+            items.retain(|i| i.published >= since);
+        }
+        if let Some(until) = self.until {
+            items.retain(|i| i.published < until);
+        }
+        if let Some(title) = &self.title {
+            items.retain(|i| title.is_match(&i.title));
+        }
+        if let Some(exclude_title) = &self.exclude_title {
+            items.retain(|i| !exclude_title.is_match(&i.title));
+        }
+        if self.dedupe {
+            feed::collapse_reruns(
+                items,
+                self.time_zone.as_ref().unwrap_or(default_time_zone),
+                self.items.locale.unwrap_or(default_locale),
+            );
+        }
+        if !self.sub_only {
+            items.retain(|i| !i.sub_only);
+        }
+        if let Some(min) = self.min_duration {
+            items.retain(|i| i.duration.is_none_or(|d| d >= min));
+        }
+        // a window reaching back before the representable dates excludes nothing
+        if let Some(oldest) = self
+            .max_age
+            .and_then(|age| OffsetDateTime::now_utc().checked_sub(age))
+        {
+            items.retain(|i| i.published >= oldest);
+        }
+    }
+
+    /// Append the length of items to their titles like ` [3h 12m]`, for readers that only list
+    /// titles. `default` applies when `title_duration` wasn't given.
+    pub fn add_durations(&self, items: &mut [FeedItem], default: bool, locale: Locale) {
+        if !self.title_duration.unwrap_or(default) {
+            return;
+        }
+        for item in items {
+            if let Some(secs) = item.duration {
+                item.title = format!("{} [{}]", item.title, locale.duration(secs));
+            }
+        }
+    }
+
+    /// Order `items` as requested by `sort`, newest first by default.
+    pub fn sort(&self, items: &mut [FeedItem]) {
+        match self.videos.sort {
+            None | Some(Sort::Time) => feed::sort_items(items),
+            Some(Sort::Views) => feed::sort_items_by_views(items),
+            // only Twitch knows what's trending, merged feeds keep their channels in turn
+            Some(Sort::Trending) => {}
+        }
+    }
+
+    /// Cut `items` down to the requested `limit`, once they are sorted.
+    pub fn truncate(&self, items: &mut Vec<FeedItem>) {
+        if let Some(limit) = self.videos.first {
+            items.truncate(limit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::Query;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn options(query: &str) -> FeedOptions {
+        FeedOptions::try_from(parse(query)).unwrap()
+    }
+
+    /// The message `query` is rejected with.
+    fn rejected(query: &str) -> String {
+        match FeedOptions::try_from(parse(query)) {
+            Err(TwitchRssError::InvalidQuery(message)) => message,
+            other => panic!("{} wasn't rejected: {:?}", query, other),
+        }
+    }
+
+    fn parse(query: &str) -> FeedQuery {
+        let uri = format!("/?{}", query).parse().unwrap();
+        Query::<FeedQuery>::try_from_uri(&uri).unwrap().0
+    }
+
+    fn item(title: &str, published: OffsetDateTime) -> FeedItem {
+        FeedItem {
+            id: title.to_string(),
+            title: title.to_string(),
+            link: String::new(),
+            description: String::new(),
+            published,
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: None,
+        }
+    }
+
+    fn filtered(query: &str, items: Vec<FeedItem>) -> Vec<String> {
+        let mut items = items;
+        options(query).filter(&mut items, &TimeZone::utc(), Locale::En);
+        items.into_iter().map(|i| i.id).collect()
+    }
+
+    #[test]
+    fn defaults_without_parameters() {
+        let parsed = options("");
+
+        assert_eq!(parsed.since, None);
+        assert!(parsed.sub_only);
+        assert!(!parsed.live);
+        assert!(!parsed.dedupe);
+        assert_eq!(parsed.videos.key(), "all");
+        assert_eq!(parsed.items.locale, None);
+    }
+
+    #[test]
+    fn parses_since() {
+        assert_eq!(
+            options("since=2024-06-01T12:00:00Z").since,
+            Some(datetime!(2024-06-01 12:00 UTC))
+        );
+        assert_eq!(
+            rejected("since=yesterday"),
+            "since=\"yesterday\" is not an RFC 3339 timestamp or a date like 2024-06-01"
+        );
+    }
+
+    #[test]
+    fn filters_since() {
+        let items = vec![
+            item("new", datetime!(2024-06-02 00:00 UTC)),
+            item("exact", datetime!(2024-06-01 12:00 UTC)),
+            item("old", datetime!(2024-05-31 00:00 UTC)),
+        ];
+
+        assert_eq!(
+            filtered("since=2024-06-01T12:00:00Z", items),
+            ["new", "exact"]
+        );
+    }
+
+    #[test]
+    fn parses_video_filters() {
+        let parsed = options("type=archive&limit=5&period=week&sort=views&lang=EN,de,en");
+        assert_eq!(parsed.videos.video_type, Some(VideoTypeFilter::Archive));
+        assert_eq!(parsed.videos.first, Some(5));
+        assert_eq!(parsed.videos.period, Some(VideoPeriod::Week));
+        assert_eq!(parsed.videos.sort, Some(Sort::Views));
+        assert_eq!(parsed.videos.languages, ["en", "de"]);
+
+        assert_eq!(options("pages=3").videos.pages, Some(3));
+        assert_eq!(options("all=1").videos.pages, Some(usize::MAX));
+        assert_eq!(options("lang=zh-hk").videos.languages, ["zh-hk"]);
+
+        assert!(rejected("type=clip").starts_with("type=\"clip\" is not one of"));
+        assert_eq!(
+            rejected("limit=0"),
+            "limit=\"0\" is not a number from 1 to 100"
+        );
+        assert_eq!(
+            rejected("limit=101"),
+            "limit=\"101\" is not a number from 1 to 100"
+        );
+        assert_eq!(rejected("pages=0"), "pages=\"0\" is not a positive number");
+        assert!(rejected("period=year").starts_with("period=\"year\""));
+        assert!(rejected("sort=random").starts_with("sort=\"random\""));
+        assert_eq!(
+            rejected("lang=english"),
+            "lang=\"english\" is not a language code like en"
+        );
+        assert_eq!(
+            rejected("lang=aa,ab,ac,ad,ae,af,ag,ah,ai,aj,ak"),
+            "lang has more than 10 languages"
+        );
+    }
+
+    #[test]
+    fn parses_flags() {
+        let parsed = options("subonly=0&live=true&dedupe=1&public=1&title_duration=0&embed=1");
+        assert!(!parsed.sub_only);
+        assert!(parsed.live);
+        assert!(parsed.dedupe);
+        assert_eq!(parsed.videos.public_only, Some(true));
+        assert_eq!(parsed.title_duration, Some(false));
+        assert!(parsed.items.embed);
+
+        assert_eq!(rejected("subonly=yes"), "subonly=\"yes\" is not 0 or 1");
+        assert_eq!(rejected("all=2"), "all=\"2\" is not 0 or 1");
+        assert_eq!(rejected("no_thumb=no"), "no_thumb=\"no\" is not 0 or 1");
+    }
+
+    #[test]
+    fn parses_item_options() {
+        let parsed = options("min_duration=1h30m&pub_date=published_at&thumb=640x360&locale=de");
+        assert_eq!(parsed.min_duration, Some(5400));
+        assert_eq!(parsed.items.pub_date, Some(PubDate::Published));
+        assert_eq!(
+            parsed.items.thumbnail_size,
+            Some(ThumbnailSize {
+                width: 640,
+                height: 360
+            })
+        );
+        assert_eq!(parsed.items.locale, Some(Locale::De));
+
+        assert_eq!(
+            rejected("min_duration=long"),
+            "min_duration=\"long\" is not a duration like 30m or 1h30m"
+        );
+        assert!(rejected("pub_date=updated_at").starts_with("pub_date=\"updated_at\""));
+        assert!(rejected("thumb=100x100").starts_with("thumb=\"100x100\" is not one of"));
+        assert_eq!(
+            rejected("locale=it"),
+            "locale=\"it\" is not one of en, de, fr, es"
+        );
+        assert!(rejected("tz=Nowhere/Special").starts_with("tz="));
+    }
+
+    #[test]
+    fn filters_sub_only_and_short_items() {
+        let at = datetime!(2024-06-01 00:00 UTC);
+        let items = vec![
+            FeedItem {
+                sub_only: true,
+                ..item("sub", at)
+            },
+            FeedItem {
+                duration: Some(600),
+                ..item("short", at)
+            },
+            FeedItem {
+                duration: Some(3600),
+                ..item("long", at)
+            },
+            item("unknown", at),
+        ];
+
+        assert_eq!(
+            filtered("subonly=0&min_duration=30m", items),
+            ["long", "unknown"]
+        );
+    }
+
+    #[test]
+    fn parses_max_age() {
+        assert_eq!(options("max_age=14d").max_age, Some(Duration::days(14)));
+        assert_eq!(options("max_age=1d12h").max_age, Some(Duration::hours(36)));
+
+        assert_eq!(
+            rejected("max_age=0"),
+            "max_age=\"0\" is not a duration like 14d or 12h"
+        );
+        assert_eq!(
+            rejected("max_age=soon"),
+            "max_age=\"soon\" is not a duration like 14d or 12h"
+        );
+    }
+
+    #[test]
+    fn filters_max_age() {
+        let now = OffsetDateTime::now_utc();
+        let items = || {
+            vec![
+                item("recent", now - Duration::days(1)),
+                item("old", now - Duration::days(30)),
+            ]
+        };
+
+        assert_eq!(filtered("max_age=14d", items()), ["recent"]);
+        // further back than any date, so nothing is too old
+        assert_eq!(filtered("max_age=100000000d", items()), ["recent", "old"]);
+    }
+
+    #[test]
+    fn parses_until_and_dates() {
+        let parsed = options("since=2024-06-01&until=2024-06-08T06:30:00Z");
+        assert_eq!(parsed.since, Some(datetime!(2024-06-01 00:00 UTC)));
+        assert_eq!(parsed.until, Some(datetime!(2024-06-08 06:30 UTC)));
+        assert_eq!(
+            options("until=2024-06-08").until,
+            Some(datetime!(2024-06-08 00:00 UTC))
+        );
+
+        assert_eq!(
+            rejected("until=2024-13-01"),
+            "until=\"2024-13-01\" is not an RFC 3339 timestamp or a date like 2024-06-01"
+        );
+        assert!(rejected("until=06/08/2024").starts_with("until=\"06/08/2024\""));
+    }
+
+    #[test]
+    fn filters_until() {
+        let items = || {
+            vec![
+                item("after", datetime!(2024-06-08 12:00 UTC)),
+                item("exact", datetime!(2024-06-08 00:00 UTC)),
+                item("within", datetime!(2024-06-03 00:00 UTC)),
+                item("before", datetime!(2024-05-31 00:00 UTC)),
+            ]
+        };
+
+        // until is exclusive, since inclusive
+        assert_eq!(filtered("until=2024-06-08", items()), ["within", "before"]);
+        assert_eq!(
+            filtered("since=2024-06-01&until=2024-06-08", items()),
+            ["within"]
+        );
+    }
+
+    #[test]
+    fn parses_title_patterns() {
+        let parsed = options("title=%5E%5BSpeedrun%5D&exclude_title=(?i)rerun");
+        assert_eq!(parsed.title.unwrap().as_str(), "^[Speedrun]");
+        assert_eq!(parsed.exclude_title.unwrap().as_str(), "(?i)rerun");
+
+        assert!(rejected("title=(unclosed")
+            .starts_with("title=\"(unclosed\" is not a valid regular expression"));
+        assert_eq!(
+            rejected(&format!("exclude_title={}", "a".repeat(201))),
+            "exclude_title is longer than 200 bytes"
+        );
+        // short, but far too large once compiled
+        assert!(rejected("title=(a{1000}){1000}")
+            .starts_with("title=\"(a{1000}){1000}\" is not a valid regular expression"));
+    }
+
+    #[test]
+    fn filters_titles() {
+        let at = datetime!(2024-06-01 00:00 UTC);
+        let items = || {
+            vec![
+                item("Speedrun any%", at),
+                item("Speedrun any% (Rerun)", at),
+                item("Just chatting", at),
+            ]
+        };
+
+        assert_eq!(filtered("title=speedrun", items()), Vec::<String>::new());
+        assert_eq!(
+            filtered("title=Speedrun&exclude_title=(?i)rerun", items()),
+            ["Speedrun any%"]
+        );
+        assert_eq!(
+            filtered("exclude_title=Speedrun", items()),
+            ["Just chatting"]
+        );
+    }
+}
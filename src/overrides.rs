@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use twitch_api2::helix::videos::VideoTypeFilter;
+
+use crate::feed::ThumbnailSize;
+
+/// Output settings for one Twitch channel, applied whenever its VOD feed is requested.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelOverride {
+    /// Replaces the feed title
+    pub title: Option<String>,
+    /// At most this many items
+    pub limit: Option<usize>,
+    /// Only videos of this type, asked of Helix like `type=`
+    #[serde(rename = "type")]
+    pub video_type: Option<VideoTypeFilter>,
+    /// Template in `TEMPLATE_DIR` used instead of the item description template
+    pub description_template: Option<String>,
+    /// Thumbnail size as `WIDTHxHEIGHT`
+    pub thumbnail_size: Option<ThumbnailSize>,
+}
+
+/// Overrides by login, read from `CHANNEL_OVERRIDES_FILE`.
+#[derive(Default)]
+pub struct Overrides(HashMap<String, ChannelOverride>);
+
+impl Overrides {
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_slice(&data)
+            .map(Self)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Settings for `login`, all unset if it has none.
+    pub fn get(&self, login: &str) -> ChannelOverride {
+        self.0.get(login).cloned().unwrap_or_default()
+    }
+}
@@ -0,0 +1,200 @@
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::response::Html;
+
+use crate::feed::{Feed, FeedItem};
+use crate::options::FeedQuery;
+use crate::render;
+use crate::timezone::TimeZone;
+use crate::{channel_feed, AppState, TwitchRssError};
+
+/// The feed variants a page points at, as `(path suffix, content type, label)`.
+const ALTERNATES: [(&str, &str, &str); 3] = [
+    ("/vod", "application/rss+xml", "RSS"),
+    ("/vod.atom", "application/atom+xml", "Atom"),
+    ("/vod.json", "application/feed+json", "JSON Feed"),
+];
+
+/// `/channel/{name}`, the items of the VOD feed as a web page, for sharing with people who don't
+/// use a feed reader. The query parameters of the feed apply, and are passed on to the feeds the
+/// page links to. `preview.html` in `TEMPLATE_DIR` replaces the page.
+pub async fn page(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Html<String>, TwitchRssError> {
+    let feed = channel_feed(&state, &name, query).await?;
+
+    // relative to the page, so the links work wherever the router is nested
+    let query = uri.query().map(|q| format!("?{}", q)).unwrap_or_default();
+    let alternates = ALTERNATES
+        .map(|(suffix, content_type, label)| {
+            (
+                format!("{}{}{}", uri.path(), suffix, query),
+                content_type,
+                label,
+            )
+        })
+        .to_vec();
+
+    #[cfg(feature = "templates")]
+    if let Some(page) = state.templates.as_ref().and_then(|t| {
+        t.render_page(
+            &feed,
+            &alternates,
+            state.config.locale,
+            &state.config.time_zone,
+        )
+    }) {
+        return Ok(Html(page));
+    }
+
+    Ok(Html(render_page(
+        &feed,
+        &alternates,
+        &state.config.time_zone,
+    )))
+}
+
+fn render_page(feed: &Feed, alternates: &[(String, &str, &str)], time_zone: &TimeZone) -> String {
+    let mut head = String::new();
+    for (href, content_type, label) in alternates {
+        head.push_str(&format!(
+            "<link rel=\"alternate\" type=\"{}\" title=\"{}\" href=\"{}\">\n",
+            content_type,
+            escape(label),
+            escape(href)
+        ));
+    }
+
+    let subscribe = alternates
+        .iter()
+        .map(|(href, _, label)| format!("<a href=\"{}\">{}</a>", escape(href), escape(label)))
+        .collect::<Vec<_>>()
+        .join(" · ");
+
+    let image = feed
+        .image
+        .as_ref()
+        .map(|url| format!("<img class=\"avatar\" src=\"{}\" alt=\"\">", escape(url)))
+        .unwrap_or_default();
+    let description = feed
+        .description
+        .as_ref()
+        .map(|d| format!("<p>{}</p>", escape(d)))
+        .unwrap_or_default();
+
+    let items = feed
+        .items
+        .iter()
+        .map(|item| render_item(item, time_zone))
+        .collect::<String>();
+
+    format!(
+        "<!DOCTYPE html>
+<html{lang}>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+{head}<style>
+body {{ font-family: sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; }}
+.avatar {{ width: 4rem; height: 4rem; border-radius: 50%; float: left; margin-right: 1rem; }}
+header {{ overflow: hidden; margin-bottom: 2rem; }}
+article {{ margin-bottom: 2rem; }}
+article img {{ max-width: 100%; }}
+time {{ color: #666; }}
+</style>
+</head>
+<body>
+<header>
+{image}<h1><a href=\"{link}\">{title}</a></h1>
+{description}<p>Subscribe: {subscribe}</p>
+</header>
+{items}</body>
+</html>
+",
+        lang = feed
+            .language
+            .as_ref()
+            .map(|l| format!(" lang=\"{}\"", escape(l)))
+            .unwrap_or_default(),
+        title = escape(&feed.title),
+        link = escape(&feed.link),
+    )
+}
+
+fn render_item(item: &FeedItem, time_zone: &TimeZone) -> String {
+    let thumbnail = item
+        .thumbnail
+        .as_ref()
+        .map(|url| {
+            format!(
+                "<a href=\"{}\"><img src=\"{}\" alt=\"\" loading=\"lazy\"></a>\n",
+                escape(&item.link),
+                escape(url)
+            )
+        })
+        .unwrap_or_default();
+    // descriptions carry text from Twitch as it is, which is only safe once it's plain text
+    let description = escape(&render::plain_text(&item.description)).replace('\n', "<br>\n");
+
+    format!(
+        "<article>
+<h2><a href=\"{link}\">{title}</a></h2>
+<time>{published}</time>
+{thumbnail}<p>{description}</p>
+</article>
+",
+        link = escape(&item.link),
+        title = escape(&item.title),
+        published = escape(&time_zone.format(item.published)),
+    )
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn escapes_what_twitch_sends() {
+        let item = FeedItem {
+            id: "1".to_string(),
+            title: "<script>alert(1)</script>".to_string(),
+            link: "https://www.twitch.tv/videos/1".to_string(),
+            description: "Showing \"tricks\"<br /><img src=x onerror=alert(1)>again".to_string(),
+            published: datetime!(2024-06-01 20:00 UTC),
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: None,
+        };
+
+        let html = render_item(&item, &TimeZone::utc());
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("Showing &quot;tricks&quot;<br>\nagain"));
+        assert!(!html.contains("onerror"));
+        assert!(html.contains("<time>2024-06-01 20:00 UTC</time>"));
+    }
+}
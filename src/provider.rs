@@ -0,0 +1,383 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use twitch_api2::helix::channels::ChannelInformation;
+use twitch_api2::helix::users::User;
+#[cfg(feature = "admin")]
+use twitch_api2::helix::videos::Sort;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::helix::HelixClient;
+
+use crate::feed::{self, Feed, FeedItem, FeedNaming, ItemStyle, ThumbnailSize};
+use crate::options::{FeedOptions, ItemOptions, VideoFilter};
+#[cfg(feature = "templates")]
+use crate::templates;
+use crate::{
+    category, expiry, get_channel, get_token, get_user, get_user_id, get_user_videos, tags,
+};
+use crate::{AppState, TwitchRssError};
+
+/// A platform videos can be fetched from.
+#[async_trait]
+pub trait VideoProvider: Send + Sync {
+    /// Platform name, used in feed titles
+    fn platform(&self) -> &'static str;
+
+    /// Page listing the videos of `channel` on the platform
+    fn channel_link(&self, channel: &str) -> String;
+
+    /// Recent videos of `channel` as feed items. `channel` has already been validated.
+    async fn videos(
+        &self,
+        state: &AppState,
+        channel: &str,
+        options: &FeedOptions,
+    ) -> Result<Vec<FeedItem>, TwitchRssError>;
+}
+
+/// Build the VOD feed of `channel` on `provider`.
+pub async fn vod_feed(
+    provider: &dyn VideoProvider,
+    state: &AppState,
+    channel: &str,
+    options: &FeedOptions,
+) -> Result<Feed, TwitchRssError> {
+    let items = provider.videos(state, channel, options).await?;
+
+    let mut feed = Feed {
+        title: options
+            .items
+            .locale
+            .unwrap_or(state.config.locale)
+            .vods_title(channel, provider.platform()),
+        link: provider.channel_link(channel),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(videos_ttl(state, channel).await),
+        items: finish_items(state, options, items).await,
+    };
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming {
+            login: channel,
+            display_name: channel,
+            platform: provider.platform(),
+            kind: "VODs",
+        },
+    );
+
+    Ok(feed)
+}
+
+/// Apply the request's filters, sort as requested, run the item script, apply the limit, add
+/// lengths to titles and attach media, in that order.
+pub async fn finish_items(
+    state: &AppState,
+    options: &FeedOptions,
+    mut items: Vec<FeedItem>,
+) -> Vec<FeedItem> {
+    options.filter(&mut items, &state.config.time_zone, state.config.locale);
+    options.sort(&mut items);
+
+    #[cfg(feature = "scripting")]
+    let mut items = match &state.script {
+        Some(script) => script.apply(items),
+        None => items,
+    };
+
+    options.truncate(&mut items);
+    options.add_durations(
+        &mut items,
+        state.config.title_duration,
+        options.items.locale.unwrap_or(state.config.locale),
+    );
+
+    #[cfg(feature = "media")]
+    if let Some(media) = &state.media {
+        media.attach(&state.cache, &mut items).await;
+    }
+
+    items
+}
+
+/// How long videos of `channel` are cached, which tracked channels can override.
+pub async fn videos_ttl(state: &AppState, channel: &str) -> Duration {
+    #[cfg(feature = "admin")]
+    if let Some(ttl) = state.tracked.get(channel).await.and_then(|c| c.ttl) {
+        return Duration::from_secs(ttl);
+    }
+    #[cfg(not(feature = "admin"))]
+    let _ = channel;
+
+    state.config.videos_ttl
+}
+
+/// Turn Helix videos of `channel` into feed items, applying the item templates if there are any.
+pub fn twitch_items(
+    state: &AppState,
+    channel: &str,
+    videos: &[Video],
+    options: &ItemOptions,
+) -> Vec<FeedItem> {
+    let items = plain_twitch_items(state, channel, videos, options);
+
+    apply_item_templates(state, channel, videos, items, options)
+}
+
+/// The size of thumbnails of `channel`, the request's taking precedence over the channel's override.
+fn thumbnail_size(state: &AppState, channel: &str, options: &ItemOptions) -> ThumbnailSize {
+    options
+        .thumbnail_size
+        .or(state.overrides.get(channel).thumbnail_size)
+        .unwrap_or_default()
+}
+
+/// [`twitch_items`] without the templates, for providers that add to the items first.
+fn plain_twitch_items(
+    state: &AppState,
+    channel: &str,
+    videos: &[Video],
+    options: &ItemOptions,
+) -> Vec<FeedItem> {
+    let style = ItemStyle {
+        size: thumbnail_size(state, channel, options),
+        no_thumbnail: options.no_thumbnails.unwrap_or(state.config.no_thumbnails),
+        // an explicitly requested size is all the reader gets
+        srcset: options.thumbnail_size.is_none(),
+        date: options.pub_date.unwrap_or(state.config.pub_date),
+        embed_parents: match options.embed {
+            true => &state.config.embed_parents,
+            false => &[],
+        },
+        locale: options.locale.unwrap_or(state.config.locale),
+    };
+
+    videos
+        .iter()
+        .map(|v| feed::video_to_item(v, &style))
+        .collect()
+}
+
+/// Render the item templates, if there are any, for `items` made from `videos` in the same order.
+fn apply_item_templates(
+    state: &AppState,
+    channel: &str,
+    videos: &[Video],
+    items: Vec<FeedItem>,
+    options: &ItemOptions,
+) -> Vec<FeedItem> {
+    #[cfg(feature = "templates")]
+    if let Some(templates) = &state.templates {
+        let size = thumbnail_size(state, channel, options);
+        let locale = options.locale.unwrap_or(state.config.locale);
+        let overrides = state.overrides.get(channel);
+        let description = overrides
+            .description_template
+            .as_deref()
+            .unwrap_or(templates::ITEM_DESCRIPTION);
+
+        return items
+            .into_iter()
+            .zip(videos)
+            .map(|(item, v)| templates.apply_to_item(v, item, size, description, locale))
+            .collect();
+    }
+    #[cfg(not(feature = "templates"))]
+    let _ = (state, channel, videos, options);
+
+    items
+}
+
+/// Recent Helix videos of `channel` matching `filter`, newest first.
+pub async fn twitch_videos(
+    state: &AppState,
+    channel: &str,
+    filter: &VideoFilter,
+) -> Result<Vec<Video>, TwitchRssError> {
+    let mut filter = filter.capped(state.config.max_video_pages);
+    filter.public_only = filter.public_only.or(Some(state.config.public_only));
+    // an explicit `type` takes precedence over the channel's override
+    filter.video_type = filter
+        .video_type
+        .or_else(|| state.overrides.get(channel).video_type);
+
+    #[cfg(feature = "eventsub")]
+    if let Some(stored) = crate::eventsub::stored_channel(state, channel).await? {
+        let mut videos = stored.videos;
+        videos.retain(|v| filter.matches(v));
+        return Ok(with_archived(state, channel, &filter, videos).await);
+    }
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        channel.into(),
+    )
+    .await?;
+
+    // the back catalog barely changes, and is expensive to fetch
+    let ttl = match filter.pages {
+        Some(_) => state.config.archive_ttl,
+        None => videos_ttl(state, channel).await,
+    };
+
+    let mut videos =
+        get_user_videos(&state.cache, ttl, &helix_client, &token, user_id, &filter).await?;
+    videos.retain(|v| filter.matches(v));
+
+    Ok(with_archived(state, channel, &filter, videos).await)
+}
+
+/// `videos` and, for tracked channels archiving theirs, the archived ones matching `filter` that
+/// Helix didn't list, like those Twitch deleted.
+#[cfg_attr(not(feature = "admin"), allow(unused_variables))]
+async fn with_archived(
+    state: &AppState,
+    channel: &str,
+    filter: &VideoFilter,
+    videos: Vec<Video>,
+) -> Vec<Video> {
+    #[cfg(feature = "admin")]
+    let videos = {
+        let mut videos = videos;
+        let archived = state.tracked.archive(channel, &videos).await;
+        videos.extend(archived.into_iter().filter(|v| filter.matches(v)));
+        // other orders are applied to the items later
+        if matches!(filter.sort, None | Some(Sort::Time)) {
+            videos.sort_by_key(|v| std::cmp::Reverse(v.created_at.to_utc()));
+        }
+        videos
+    };
+
+    videos
+}
+
+/// How long archives of `channel` are kept by Twitch.
+pub async fn twitch_retention(state: &AppState, channel: &str) -> Result<Duration, TwitchRssError> {
+    let user = twitch_user(state, channel).await?;
+
+    Ok(expiry::retention(user.broadcaster_type.as_ref()))
+}
+
+/// Channel information of `channel`, `None` in zero-poll mode which only stores the user.
+pub async fn twitch_channel(
+    state: &AppState,
+    channel: &str,
+) -> Result<Option<ChannelInformation>, TwitchRssError> {
+    #[cfg(feature = "eventsub")]
+    if crate::eventsub::stored_channel(state, channel)
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    get_channel(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        channel.into(),
+    )
+    .await
+    .map(Some)
+}
+
+/// The Helix user behind `channel`.
+pub async fn twitch_user(state: &AppState, channel: &str) -> Result<User, TwitchRssError> {
+    #[cfg(feature = "eventsub")]
+    if let Some(stored) = crate::eventsub::stored_channel(state, channel).await? {
+        return Ok(stored.user);
+    }
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    get_user(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        channel.into(),
+    )
+    .await
+}
+
+pub struct Twitch;
+
+#[async_trait]
+impl VideoProvider for Twitch {
+    fn platform(&self) -> &'static str {
+        "Twitch"
+    }
+
+    fn channel_link(&self, channel: &str) -> String {
+        format!("https://www.twitch.tv/{}/videos", channel)
+    }
+
+    async fn videos(
+        &self,
+        state: &AppState,
+        channel: &str,
+        options: &FeedOptions,
+    ) -> Result<Vec<FeedItem>, TwitchRssError> {
+        let (videos, retention, tags) = tokio::try_join!(
+            twitch_videos(state, channel, &options.videos),
+            twitch_retention(state, channel),
+            tags::channel_tags(state, channel)
+        )?;
+
+        let items = category::annotate(
+            state,
+            channel,
+            plain_twitch_items(state, channel, &videos, &options.items),
+            &videos,
+            options.items.locale.unwrap_or(state.config.locale),
+        )
+        .await?;
+        // templated once the category and tags are known, so templates can show them
+        let items = apply_item_templates(
+            state,
+            channel,
+            &videos,
+            tags::annotate(items, &tags),
+            &options.items,
+        );
+
+        Ok(expiry::annotate(
+            items,
+            &videos,
+            retention,
+            options.items.locale.unwrap_or(state.config.locale),
+        ))
+    }
+}
@@ -0,0 +1,180 @@
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use crate::cache::CacheStore;
+use crate::config::RedisConfig;
+
+/// Keys of this server are prefixed, so a Redis shared with other applications stays readable.
+const PREFIX: &str = "twitch_rss:";
+
+/// How long a command may take before the connection is dropped and the cache treated as empty.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A [`CacheStore`] in Redis, so the cache outlives the process. Lambda instances, restarts and
+/// several servers behind a load balancer then share it.
+///
+/// Commands are sent one at a time over a single connection, opened on first use and again after
+/// errors. Redis being unreachable only makes every lookup a miss.
+pub struct RedisStore {
+    config: RedisConfig,
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Nil,
+    Bulk(Vec<u8>),
+    /// Status and integer replies, which only say the command worked
+    Ok,
+}
+
+impl RedisStore {
+    pub fn new(config: RedisConfig) -> Self {
+        Self {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    async fn command(&self, args: &[&[u8]]) -> Option<Reply> {
+        let mut connection = self.connection.lock().await;
+        let result = tokio::time::timeout(TIMEOUT, async {
+            if connection.is_none() {
+                *connection = Some(self.connect().await?);
+            }
+            send(connection.as_mut().expect("connected above"), args).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")));
+
+        match result {
+            Ok(reply) => Some(reply),
+            Err(e) => {
+                println!("redis at {}: {}", self.config.address, e);
+                *connection = None;
+                None
+            }
+        }
+    }
+
+    async fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let mut stream = BufReader::new(TcpStream::connect(&self.config.address).await?);
+        if let Some(password) = &self.config.password {
+            let mut auth: Vec<&[u8]> = vec![b"AUTH"];
+            auth.extend(self.config.username.as_ref().map(|u| u.as_bytes()));
+            auth.push(password.as_bytes());
+            send(&mut stream, &auth).await?;
+        }
+        if self.config.db != 0 {
+            let db = self.config.db.to_string();
+            send(&mut stream, &[b"SELECT", db.as_bytes()]).await?;
+        }
+        Ok(stream)
+    }
+}
+
+#[async_trait]
+impl CacheStore for RedisStore {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let key = format!("{}{}", PREFIX, key);
+        match self.command(&[b"GET", key.as_bytes()]).await? {
+            Reply::Bulk(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let key = format!("{}{}", PREFIX, key);
+        let millis = ttl.as_millis().max(1).to_string();
+        self.command(&[b"SET", key.as_bytes(), &value, b"PX", millis.as_bytes()])
+            .await;
+    }
+
+    async fn invalidate(&self, key: &str) {
+        let key = format!("{}{}", PREFIX, key);
+        self.command(&[b"DEL", key.as_bytes()]).await;
+    }
+}
+
+async fn send(stream: &mut BufReader<TcpStream>, args: &[&[u8]]) -> io::Result<Reply> {
+    stream.get_mut().write_all(&encode(args)).await?;
+    read_reply(stream).await
+}
+
+/// A command in RESP, an array of bulk strings.
+fn encode(args: &[&[u8]]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        command.extend_from_slice(arg);
+        command.extend_from_slice(b"\r\n");
+    }
+    command
+}
+
+/// Errors become `Err`, so they drop the connection like a broken one. The commands sent never
+/// reply with arrays.
+async fn read_reply(stream: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Reply> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed",
+        ));
+    }
+    let line = line.trim_end_matches("\r\n");
+    let rest = line.get(1..).unwrap_or_default();
+
+    match line.as_bytes().first() {
+        Some(b'+' | b':') => Ok(Reply::Ok),
+        Some(b'-') => Err(io::Error::other(rest.to_string())),
+        Some(b'$') => {
+            let Ok(len) = usize::try_from(rest.parse::<i64>().map_err(|_| invalid(line))?) else {
+                return Ok(Reply::Nil);
+            };
+            let mut value = vec![0; len + 2];
+            stream.read_exact(&mut value).await?;
+            value.truncate(len);
+            Ok(Reply::Bulk(value))
+        }
+        _ => Err(invalid(line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_commands() {
+        assert_eq!(
+            encode(&[b"SET", b"key", b"a\r\nb", b"PX", b"1000"]),
+            b"*5\r\n$3\r\nSET\r\n$3\r\nkey\r\n$4\r\na\r\nb\r\n$2\r\nPX\r\n$4\r\n1000\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_replies() {
+        let mut replies: &[u8] = b"+OK\r\n$4\r\na\r\nb\r\n$-1\r\n:1\r\n-ERR wrong\r\n";
+
+        assert_eq!(read_reply(&mut replies).await.unwrap(), Reply::Ok);
+        assert_eq!(
+            read_reply(&mut replies).await.unwrap(),
+            Reply::Bulk(b"a\r\nb".to_vec())
+        );
+        assert_eq!(read_reply(&mut replies).await.unwrap(), Reply::Nil);
+        assert_eq!(read_reply(&mut replies).await.unwrap(), Reply::Ok);
+        assert_eq!(
+            read_reply(&mut replies).await.unwrap_err().to_string(),
+            "ERR wrong"
+        );
+        assert!(read_reply(&mut replies).await.is_err());
+    }
+}
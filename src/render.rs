@@ -0,0 +1,654 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use atom_syndication::{
+    ContentBuilder, Entry, EntryBuilder, FeedBuilder, FixedDateTime, GeneratorBuilder, LinkBuilder,
+};
+use axum::http::header;
+use axum::response::IntoResponse;
+use rss::extension::itunes::{
+    self, ITunesChannelExtension, ITunesChannelExtensionBuilder, ITunesItemExtension,
+    ITunesItemExtensionBuilder,
+};
+use rss::extension::{Extension, ExtensionBuilder, ExtensionMap};
+use rss::{
+    CategoryBuilder, ChannelBuilder, EnclosureBuilder, GuidBuilder, Image, ImageBuilder, Item,
+    ItemBuilder,
+};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::conditional;
+use crate::config::ChannelMetadata;
+use crate::feed::{Feed, FeedItem};
+use crate::json_feed::JsonFeedRenderer;
+use crate::TwitchRssError;
+
+/// What RSS item guids are made of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GuidStyle {
+    /// The item id, like the video id
+    #[default]
+    Id,
+    /// The item link, marked as a permalink
+    Url,
+    /// `login:id`, unique across channels
+    LoginId,
+}
+
+impl FromStr for GuidStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "url" => Ok(Self::Url),
+            "login_id" => Ok(Self::LoginId),
+            _ => Err(format!("{:?} is not id, url or login_id", s)),
+        }
+    }
+}
+
+impl GuidStyle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Url => "url",
+            Self::LoginId => "login_id",
+        }
+    }
+}
+
+/// Turns a [`Feed`] into a document in some output format.
+pub trait FeedRenderer: Send + Sync {
+    /// Value of the `Content-Type` header for rendered documents
+    fn content_type(&self) -> &str;
+
+    fn render(&self, feed: &Feed) -> Vec<u8>;
+}
+
+/// Available renderers by format name, in the order they were registered. Renderers registered
+/// earlier win `Accept` headers naming a content type several of them produce.
+pub struct Renderers(Vec<(String, Arc<dyn FeedRenderer>)>);
+
+impl Renderers {
+    /// The built-in renderers, with `metadata` added to every channel.
+    pub fn new(metadata: ChannelMetadata) -> Self {
+        let mut renderers = Self(Vec::new());
+        renderers.register(
+            "rss",
+            RssRenderer {
+                metadata: metadata.clone(),
+                itunes: false,
+            },
+        );
+        renderers.register(
+            "podcast",
+            RssRenderer {
+                metadata: metadata.clone(),
+                itunes: true,
+            },
+        );
+        renderers.register(
+            "atom",
+            AtomRenderer {
+                metadata: metadata.clone(),
+            },
+        );
+        renderers.register("json", JsonFeedRenderer { metadata });
+        renderers
+    }
+
+    /// Add a renderer, replacing any existing one for the same format.
+    pub fn register(&mut self, format: impl Into<String>, renderer: impl FeedRenderer + 'static) {
+        let format = format.into();
+        let renderer: Arc<dyn FeedRenderer> = Arc::new(renderer);
+        match self.0.iter_mut().find(|(f, _)| *f == format) {
+            Some((_, existing)) => *existing = renderer,
+            None => self.0.push((format, renderer)),
+        }
+    }
+
+    pub fn get(&self, format: &str) -> Option<Arc<dyn FeedRenderer>> {
+        self.0
+            .iter()
+            .find(|(f, _)| f == format)
+            .map(|(_, r)| r.clone())
+    }
+
+    /// The renderer for a response: the one named by `format` if there is one, otherwise the most
+    /// preferred media type of the `accept` header that a renderer produces, otherwise RSS.
+    pub fn negotiate(
+        &self,
+        format: Option<&str>,
+        accept: Option<&str>,
+    ) -> Result<Arc<dyn FeedRenderer>, TwitchRssError> {
+        if let Some(format) = format {
+            return self.get(format).ok_or_else(|| {
+                let mut known = self.0.iter().map(|(f, _)| f.as_str()).collect::<Vec<_>>();
+                known.sort_unstable();
+                TwitchRssError::InvalidQuery(format!(
+                    "format={:?} is not one of {}",
+                    format,
+                    known.join(", ")
+                ))
+            });
+        }
+
+        let preferred = accept
+            .into_iter()
+            .flat_map(accepted_types)
+            .find_map(|media_type| {
+                self.0
+                    .iter()
+                    .map(|(_, r)| r)
+                    .find(|r| r.content_type().eq_ignore_ascii_case(media_type))
+            });
+
+        Ok(preferred
+            .cloned()
+            .unwrap_or_else(|| self.get("rss").expect("rss renderer is always registered")))
+    }
+}
+
+/// Media types of an `Accept` header, most preferred first. Types with `q=0` are left out, wildcards
+/// are kept but never match a renderer.
+fn accepted_types(accept: &str) -> Vec<&str> {
+    let mut types = accept
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';');
+            let media_type = params.next()?.trim();
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((media_type, quality))
+        })
+        .collect::<Vec<_>>();
+    // stable, so equally preferred types keep the order they were listed in
+    types.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    types.into_iter().map(|(t, _)| t).collect()
+}
+
+/// A rendered document, ready to be sent with its content type.
+pub struct Rendered {
+    pub content_type: String,
+    pub body: Vec<u8>,
+    /// Value of the `Last-Modified` header, when the newest item was published
+    pub last_modified: Option<String>,
+}
+
+impl Rendered {
+    pub fn new(renderer: &dyn FeedRenderer, feed: &Feed) -> Self {
+        Self {
+            content_type: renderer.content_type().to_string(),
+            body: renderer.render(feed),
+            last_modified: newest(feed).and_then(conditional::http_date),
+        }
+    }
+}
+
+impl IntoResponse for Rendered {
+    fn into_response(self) -> axum::response::Response {
+        let mut response = ([(header::CONTENT_TYPE, self.content_type)], self.body).into_response();
+        if let Some(value) = self
+            .last_modified
+            .and_then(|l| header::HeaderValue::from_str(&l).ok())
+        {
+            response.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+        response
+    }
+}
+
+/// [Media RSS](https://www.rssboard.org/media-rss), which readers use for previews.
+const MEDIA_NAMESPACE: &str = "http://search.yahoo.com/mrss/";
+
+/// Atom, only for the `atom:link` pointing RSS feeds at themselves.
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// [RSS content module](https://web.resource.org/rss/1.0/modules/content/), for
+/// `content:encoded`.
+const CONTENT_NAMESPACE: &str = "http://purl.org/rss/1.0/modules/content/";
+
+pub struct RssRenderer {
+    metadata: ChannelMetadata,
+    /// Add the iTunes podcast tags, so podcast apps can subscribe to the feed
+    itunes: bool,
+}
+
+impl FeedRenderer for RssRenderer {
+    fn content_type(&self) -> &str {
+        "application/rss+xml"
+    }
+
+    fn render(&self, feed: &Feed) -> Vec<u8> {
+        let mut namespaces = BTreeMap::new();
+        namespaces.insert("media".to_string(), MEDIA_NAMESPACE.to_string());
+        namespaces.insert("content".to_string(), CONTENT_NAMESPACE.to_string());
+        if feed.self_link.is_some() {
+            namespaces.insert("atom".to_string(), ATOM_NAMESPACE.to_string());
+        }
+        if self.itunes {
+            namespaces.insert("itunes".to_string(), itunes::NAMESPACE.to_string());
+        }
+
+        ChannelBuilder::default()
+            .namespaces(namespaces)
+            .title(feed.title.clone())
+            .link(feed.link.clone())
+            // required by RSS, so feeds without a description repeat the title
+            .description(
+                feed.description
+                    .clone()
+                    .unwrap_or_else(|| feed.title.clone()),
+            )
+            .language(feed.language.clone())
+            .last_build_date(last_build_date(feed))
+            // in minutes, rounded up so readers don't poll before the cache expires
+            .ttl(
+                feed.ttl
+                    .map(|ttl| ttl.as_secs().div_ceil(60).max(1).to_string()),
+            )
+            .image(rss_image(feed))
+            .copyright(self.metadata.copyright.clone())
+            .generator(self.metadata.generator.clone())
+            .webmaster(self.metadata.web_master.clone())
+            .managing_editor(self.metadata.managing_editor.clone())
+            .itunes_ext(self.itunes.then(|| itunes_channel(feed)))
+            .extensions(self_link_extension(
+                feed,
+                self.content_type(),
+                websub_hub(feed),
+            ))
+            .items(
+                feed.items
+                    .iter()
+                    .map(|i| rss_item(i, self.itunes, self.metadata.guid))
+                    .collect::<Vec<_>>(),
+            )
+            .build()
+            .to_string()
+            .into_bytes()
+    }
+}
+
+/// Feeds change when a video is added, so they were last built when their newest item was
+/// published. Scheduled broadcasts are dated in the future, which a feed can't be built in.
+fn newest(feed: &Feed) -> Option<OffsetDateTime> {
+    feed.items
+        .iter()
+        .map(|i| i.published)
+        .max()
+        .map(|newest| newest.min(OffsetDateTime::now_utc()))
+}
+
+fn last_build_date(feed: &Feed) -> Option<String> {
+    newest(feed).and_then(|newest| {
+        newest
+            .format(&time::format_description::well_known::Rfc2822)
+            .ok()
+    })
+}
+
+/// The WebSub hub `feed` advertises. Subscribers need the feed's own URL to subscribe with, so
+/// feeds without a self link have none.
+pub fn websub_hub(feed: &Feed) -> Option<&str> {
+    feed.self_link.as_ref().and(feed.hub.as_deref())
+}
+
+/// `atom:link rel="self"`, which validators expect of every feed, and `rel="hub"` for WebSub.
+fn self_link_extension(feed: &Feed, content_type: &str, hub: Option<&str>) -> ExtensionMap {
+    let Some(href) = &feed.self_link else {
+        return ExtensionMap::new();
+    };
+
+    let mut links = vec![ExtensionBuilder::default()
+        .name("atom:link".to_string())
+        .attrs(BTreeMap::from([
+            ("href".to_string(), href.clone()),
+            ("rel".to_string(), "self".to_string()),
+            ("type".to_string(), content_type.to_string()),
+        ]))
+        .build()];
+    if let Some(hub) = hub {
+        links.push(
+            ExtensionBuilder::default()
+                .name("atom:link".to_string())
+                .attrs(BTreeMap::from([
+                    ("href".to_string(), hub.to_string()),
+                    ("rel".to_string(), "hub".to_string()),
+                ]))
+                .build(),
+        );
+    }
+
+    BTreeMap::from([(
+        "atom".to_string(),
+        BTreeMap::from([("link".to_string(), links)]),
+    )])
+}
+
+/// Readers show the image next to the subscription, it has to link back to the channel.
+fn rss_image(feed: &Feed) -> Option<Image> {
+    feed.image.as_ref().map(|url| {
+        ImageBuilder::default()
+            .url(url.clone())
+            .title(feed.title.clone())
+            .link(feed.link.clone())
+            .build()
+    })
+}
+
+fn itunes_channel(feed: &Feed) -> ITunesChannelExtension {
+    ITunesChannelExtensionBuilder::default()
+        .image(feed.image.clone())
+        .build()
+}
+
+fn itunes_item(input: &FeedItem) -> ITunesItemExtension {
+    ITunesItemExtensionBuilder::default()
+        .image(input.thumbnail.clone())
+        .duration(input.duration.map(itunes_duration))
+        .summary(Some(plain_text(&input.description)))
+        .build()
+}
+
+/// `H:MM:SS`, which every podcast app understands.
+fn itunes_duration(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// For elements readers show as plain text, line breaks are kept and other markup dropped.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        if rest[start..start + end].starts_with("<br") {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    text.trim().to_string()
+}
+
+/// Whether `link` is of a single video or clip, so no other item links there.
+fn is_permalink(link: &str) -> bool {
+    link.starts_with("https://clips.twitch.tv/")
+        || link.starts_with("https://www.twitch.tv/videos/")
+        || link.contains("/clip/")
+}
+
+fn rss_item(input: &FeedItem, itunes: bool, guid: GuidStyle) -> Item {
+    let guid = match (guid, &input.channel) {
+        (GuidStyle::Url, _) if is_permalink(&input.link) => GuidBuilder::default()
+            .value(input.link.clone())
+            .permalink(true)
+            .build(),
+        // live items link to the channel, like every other stream of it
+        (GuidStyle::Url | GuidStyle::LoginId, Some(channel)) => GuidBuilder::default()
+            .value(format!("{}:{}", channel, input.id))
+            .permalink(false)
+            .build(),
+        // items that aren't from a single channel, like clips, keep their id
+        _ => GuidBuilder::default()
+            .value(input.id.clone())
+            .permalink(false)
+            .build(),
+    };
+
+    let published = input
+        .published
+        .format(&time::format_description::well_known::Rfc2822)
+        .ok();
+
+    ItemBuilder::default()
+        .guid(guid)
+        .pub_date(published)
+        .title(input.title.clone())
+        .link(input.link.clone())
+        // some readers escape or strip markup in `description`, the HTML goes in `content:encoded`
+        .description(plain_text(&input.description))
+        .content(input.description.clone())
+        .categories(
+            input
+                .categories()
+                .map(|name| CategoryBuilder::default().name(name.clone()).build())
+                .collect::<Vec<_>>(),
+        )
+        // the length isn't known without downloading the media, 0 is what readers expect then
+        .enclosure(input.enclosure.as_ref().map(|e| {
+            EnclosureBuilder::default()
+                .url(e.url.clone())
+                .mime_type(e.mime_type.clone())
+                .length("0".to_string())
+                .build()
+        }))
+        .itunes_ext(itunes.then(|| itunes_item(input)))
+        .extensions(media_extensions(input))
+        .build()
+}
+
+/// `media:thumbnail` with the preview image and, for recordings, `media:content` pointing at the
+/// video.
+fn media_extensions(input: &FeedItem) -> ExtensionMap {
+    let mut elements = BTreeMap::new();
+
+    if let Some(thumbnail) = &input.thumbnail {
+        elements.insert(
+            "thumbnail".to_string(),
+            vec![media_element("thumbnail", [("url", thumbnail.clone())])],
+        );
+    }
+
+    // only recordings are a video to point at, not e.g. scheduled broadcasts
+    if let Some(duration) = input.duration {
+        elements.insert(
+            "content".to_string(),
+            vec![media_element(
+                "content",
+                [
+                    ("url", input.link.clone()),
+                    ("medium", "video".to_string()),
+                    ("duration", duration.to_string()),
+                ],
+            )],
+        );
+    }
+
+    BTreeMap::from([("media".to_string(), elements)])
+}
+
+fn media_element<const N: usize>(name: &str, attrs: [(&str, String); N]) -> Extension {
+    ExtensionBuilder::default()
+        .name(format!("media:{}", name))
+        .attrs(
+            attrs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<BTreeMap<_, _>>(),
+        )
+        .build()
+}
+
+pub struct AtomRenderer {
+    metadata: ChannelMetadata,
+}
+
+impl FeedRenderer for AtomRenderer {
+    fn content_type(&self) -> &str {
+        "application/atom+xml"
+    }
+
+    fn render(&self, feed: &Feed) -> Vec<u8> {
+        // a feed is as new as its newest item, an empty one only as new as this request
+        let updated = feed
+            .items
+            .iter()
+            .map(|i| i.published)
+            .max()
+            .unwrap_or_else(OffsetDateTime::now_utc);
+
+        FeedBuilder::default()
+            .title(feed.title.clone())
+            .id(feed.link.clone())
+            .updated(atom_date(updated))
+            .links(
+                std::iter::once(LinkBuilder::default().href(feed.link.clone()).build())
+                    .chain(feed.self_link.as_ref().map(|href| {
+                        LinkBuilder::default()
+                            .href(href.clone())
+                            .rel("self".to_string())
+                            .mime_type(Some(self.content_type().to_string()))
+                            .build()
+                    }))
+                    .chain(websub_hub(feed).map(|hub| {
+                        LinkBuilder::default()
+                            .href(hub.to_string())
+                            .rel("hub".to_string())
+                            .build()
+                    }))
+                    .collect::<Vec<_>>(),
+            )
+            .subtitle(feed.description.clone().map(Into::into))
+            .lang(feed.language.clone())
+            .icon(self.metadata.icon.clone())
+            .logo(feed.image.clone())
+            .rights(self.metadata.copyright.clone().map(Into::into))
+            .generator(
+                self.metadata
+                    .generator
+                    .clone()
+                    .map(|g| GeneratorBuilder::default().value(g).build()),
+            )
+            .entries(feed.items.iter().map(atom_entry).collect::<Vec<_>>())
+            .build()
+            .to_string()
+            .into_bytes()
+    }
+}
+
+/// Entry ids are made of the item id, links aren't unique: every went-live item and the pinned
+/// live item link to the channel.
+fn atom_entry(input: &FeedItem) -> Entry {
+    let content = ContentBuilder::default()
+        .value(input.description.clone())
+        .content_type("html".to_string())
+        .build();
+
+    EntryBuilder::default()
+        .id(atom_id(input))
+        .title(input.title.clone())
+        .updated(atom_date(input.published))
+        .published(atom_date(input.published))
+        .links(
+            std::iter::once(LinkBuilder::default().href(input.link.clone()).build())
+                .chain(input.enclosure.as_ref().map(|e| {
+                    LinkBuilder::default()
+                        .href(e.url.clone())
+                        .rel("enclosure".to_string())
+                        .mime_type(Some(e.mime_type.clone()))
+                        .build()
+                }))
+                .collect::<Vec<_>>(),
+        )
+        .categories(
+            input
+                .categories()
+                .map(|term| {
+                    atom_syndication::CategoryBuilder::default()
+                        .term(term.clone())
+                        .build()
+                })
+                .collect::<Vec<_>>(),
+        )
+        .content(content)
+        .build()
+}
+
+fn atom_id(input: &FeedItem) -> String {
+    format!("urn:twitch-rss:{}", input.id)
+}
+
+fn atom_date(date: OffsetDateTime) -> FixedDateTime {
+    let formatted = date.format(&Rfc3339).expect("feed dates are representable");
+    FixedDateTime::parse_from_rfc3339(&formatted).expect("formatted as RFC 3339 above")
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn item(id: &str, link: &str) -> FeedItem {
+        FeedItem {
+            id: id.to_string(),
+            title: "Live".to_string(),
+            link: link.to_string(),
+            description: String::new(),
+            published: datetime!(2024-06-01 20:00 UTC),
+            sub_only: false,
+            duration: None,
+            thumbnail: None,
+            views: None,
+            category: None,
+            enclosure: None,
+            tags: Vec::new(),
+            channel: Some("foo".to_string()),
+        }
+    }
+
+    #[test]
+    fn atom_ids_are_unique_per_item() {
+        let feed = Feed {
+            title: "foo".to_string(),
+            link: "https://www.twitch.tv/foo".to_string(),
+            image: None,
+            description: None,
+            language: None,
+            self_link: None,
+            hub: None,
+            ttl: None,
+            items: vec![
+                item("live-1", "https://www.twitch.tv/foo"),
+                item("live-2", "https://www.twitch.tv/foo"),
+            ],
+        };
+
+        let atom = String::from_utf8(
+            AtomRenderer {
+                metadata: ChannelMetadata::default(),
+            }
+            .render(&feed),
+        )
+        .unwrap();
+        assert!(atom.contains("<id>urn:twitch-rss:live-1</id>"));
+        assert!(atom.contains("<id>urn:twitch-rss:live-2</id>"));
+    }
+
+    #[test]
+    fn url_guids_fall_back_for_channel_links() {
+        let guid = |item: &FeedItem| rss_item(item, false, GuidStyle::Url).guid.unwrap();
+
+        let first = guid(&item("live-1", "https://www.twitch.tv/foo"));
+        let second = guid(&item("live-2", "https://www.twitch.tv/foo"));
+        assert_eq!(first.value, "foo:live-1");
+        assert_eq!(second.value, "foo:live-2");
+        assert!(!first.permalink);
+
+        let video = guid(&item("1", "https://www.twitch.tv/videos/1"));
+        assert_eq!(video.value, "https://www.twitch.tv/videos/1");
+        assert!(video.permalink);
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use axum::body::{self, Body};
+use axum::extract::rejection::RawPathParamsRejection;
+use axum::extract::{RawPathParams, Request, State};
+use axum::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::cache::{response_key, responses_generation, UrlChannel};
+use crate::{login, AppState};
+
+/// How long other requests are served a stale response before one of them starts rendering it
+/// again, in seconds. Covers the background render, so a feed is only rendered once at a time.
+const REFRESH_TIMEOUT: i64 = 30;
+
+/// A rendered feed as it was sent. Feeds are text, so the body is kept as a string, which stores
+/// far smaller as JSON than bytes would.
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    /// Unix time until which the response is served as it is
+    fresh_until: i64,
+    /// Unix time until which it's served while being rendered again
+    stale_until: i64,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// Keep successful responses for `RESPONSE_CACHE_TTL` and answer the same URL from that, so
+/// readers polling a feed don't each have it rendered again. Only runs with a TTL set.
+///
+/// For `RESPONSE_STALE_TTL` after that, the old response is still sent right away while a
+/// background task renders the feed again for the next reader.
+///
+/// Entries are keyed by the generation of the channel in the URL, which EventSub notifications and
+/// purges bump to drop them.
+///
+/// Cached responses still go through the other layers, which add the `ETag` and answer
+/// conditional requests.
+pub async fn serve(
+    State(state): State<AppState>,
+    params: Result<RawPathParams, RawPathParamsRejection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.config.response_ttl.is_none() || request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let accept = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+    let generation = match params.ok().as_ref().and_then(url_channel) {
+        Some(channel) => responses_generation(&state.cache, &channel).await,
+        None => 0,
+    };
+    let key = response_key(accept, generation, path_and_query);
+
+    let Some(cached) = state.cache.get::<CachedResponse>(&key).await else {
+        return render(&state, &key, request, next).await;
+    };
+    let now = now();
+    if now >= cached.fresh_until {
+        // the refresh claims the entry first, so readers in the meantime don't start their own
+        let claimed = CachedResponse {
+            fresh_until: now + REFRESH_TIMEOUT,
+            ..cached.clone()
+        };
+        state
+            .cache
+            .set(&key, &claimed, seconds(cached.stale_until - now))
+            .await;
+
+        println!("refreshing {}", path_and_query);
+        let state = state.clone();
+        tokio::spawn(async move {
+            render(&state, &key, request, next).await;
+        });
+    }
+    cached.into_response()
+}
+
+/// Run the request and keep the response if it worked.
+async fn render(state: &AppState, key: &str, request: Request, next: Next) -> Response {
+    let Some(ttl) = state.config.response_ttl else {
+        return next.run(request).await;
+    };
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(text) = String::from_utf8(bytes.to_vec()) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    let now = now();
+    let kept = ttl + state.config.response_stale;
+    let cached = CachedResponse {
+        fresh_until: now + ttl.as_secs() as i64,
+        stale_until: now + kept.as_secs() as i64,
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: text,
+    };
+    state.cache.set(key, &cached, kept).await;
+    Response::from_parts(parts, Body::from(cached.body))
+}
+
+/// The channel named in the path, if any. Logins are normalized like the feeds do, so every
+/// spelling shares the generation.
+fn url_channel(params: &RawPathParams) -> Option<UrlChannel> {
+    params.iter().find_map(|(key, value)| match key {
+        "name" => login::parse_login(value).ok().map(UrlChannel::Login),
+        "user_id" => Some(UrlChannel::UserId(value.trim().to_string())),
+        _ => None,
+    })
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+/// A store keeps entries for at least a second.
+fn seconds(secs: i64) -> Duration {
+    Duration::from_secs(secs.max(1) as u64)
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                response.headers_mut().append(name, value);
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_headers() {
+        let cached = CachedResponse {
+            fresh_until: 0,
+            stale_until: 0,
+            headers: vec![
+                (
+                    "content-type".to_string(),
+                    "application/rss+xml".to_string(),
+                ),
+                ("vary".to_string(), "accept".to_string()),
+                ("not a header".to_string(), "dropped".to_string()),
+            ],
+            body: "<rss></rss>".to_string(),
+        };
+
+        let response = cached.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/rss+xml"
+        );
+        assert_eq!(response.headers()[header::VARY], "accept");
+        assert_eq!(response.headers().len(), 2);
+    }
+}
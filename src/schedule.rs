@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use axum::extract::{OriginalUri, Path, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use twitch_api2::helix::schedule::Segment;
+use twitch_api2::helix::HelixClient;
+use twitch_api2::types::Timestamp;
+
+use crate::feed::{Feed, FeedItem, FeedNaming};
+use crate::i18n::Locale;
+use crate::ical::{self, Event};
+use crate::options::{self, LocaleQuery};
+use crate::render::Rendered;
+use crate::timezone::TimeZone;
+use crate::{get_schedule, get_token, get_user_id, login, render_feed, AppState, TwitchRssError};
+
+#[derive(Deserialize)]
+pub struct ScheduleQuery {
+    /// Time zone of the times in descriptions, like `Europe/Berlin`
+    tz: Option<String>,
+    /// Language of generated text, one of [`LOCALES`](crate::i18n::LOCALES)
+    locale: Option<String>,
+}
+
+/// A scheduled broadcast, the parts of a Helix [`Segment`] that are kept in the cache.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledSegment {
+    pub id: String,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    pub title: String,
+    pub category: Option<String>,
+    /// Repeats weekly
+    pub is_recurring: bool,
+    /// Set when this broadcast was canceled
+    #[serde(default)]
+    pub canceled_until: Option<Timestamp>,
+}
+
+impl ScheduledSegment {
+    pub fn is_canceled(&self) -> bool {
+        self.canceled_until.is_some()
+    }
+}
+
+impl From<Segment> for ScheduledSegment {
+    fn from(segment: Segment) -> Self {
+        Self {
+            id: segment.id.to_string(),
+            start_time: segment.start_time,
+            end_time: segment.end_time,
+            title: segment.title,
+            category: segment.category.map(|c| c.name),
+            is_recurring: segment.is_recurring,
+            canceled_until: segment.canceled_until,
+        }
+    }
+}
+
+/// `/channel/{name}/schedule`, an item for every upcoming broadcast on the channel's schedule.
+pub async fn feed(
+    Path(name): Path<String>,
+    Query(query): Query<ScheduleQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let time_zone = match query.tz {
+        Some(tz) => {
+            TimeZone::load(&tz).map_err(|e| TwitchRssError::InvalidQuery(format!("tz={}", e)))?
+        }
+        None => state.config.time_zone.clone(),
+    };
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+    let segments = segments(&state, &name).await?;
+
+    let mut feed = Feed {
+        title: locale.schedule_title(&name, "Twitch"),
+        link: format!("https://www.twitch.tv/{}/schedule", name),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.schedule_ttl),
+        items: segments
+            .iter()
+            .filter(|s| !s.is_canceled())
+            .map(|s| segment_to_item(&name, s, &time_zone, locale))
+            .collect(),
+    };
+    feed.apply_templates(
+        &state.config.channel,
+        &FeedNaming::twitch(&name, "schedule"),
+    );
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `/channel/{name}/schedule.ics`, the same schedule as a calendar to subscribe to.
+pub async fn calendar(
+    Path(name): Path<String>,
+    Query(query): Query<LocaleQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, TwitchRssError> {
+    let name = login::parse_login(&name)?;
+    let locale = options::locale(query.locale.as_deref())?.unwrap_or(state.config.locale);
+    let segments = segments(&state, &name).await?;
+
+    let body = ical::calendar(
+        &locale.schedule_title(&name, "Twitch"),
+        &events(&name, &segments),
+    );
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        body,
+    ))
+}
+
+/// Helix lists every upcoming week of a recurring segment on its own. Those are folded back into a
+/// single weekly event from the first occurrence to the last one listed, with the weeks missing or
+/// canceled in between skipped. Ending the series there keeps a slot that moves, as it does in UTC
+/// when daylight saving time starts or ends, or is dropped from repeating forever.
+fn events(channel: &str, segments: &[ScheduledSegment]) -> Vec<Event> {
+    let mut sorted = segments
+        .iter()
+        .filter(|s| !s.is_canceled())
+        .collect::<Vec<_>>();
+    sorted.sort_by_key(|s| s.start_time.to_utc());
+
+    let mut events = Vec::new();
+    // occurrences of each series so far, by the index of its event
+    let mut series = HashMap::new();
+    let mut occurrences: HashMap<usize, Vec<OffsetDateTime>> = HashMap::new();
+
+    for segment in sorted {
+        let start = segment.start_time.to_utc();
+        let end = segment.end_time.to_utc();
+
+        let uid = if segment.is_recurring {
+            let key = (
+                start.weekday(),
+                start.time(),
+                end - start,
+                &segment.title,
+                &segment.category,
+            );
+            if let Some(&index) = series.get(&key) {
+                occurrences.entry(index).or_default().push(start);
+                continue;
+            }
+            series.insert(key, events.len());
+            format!(
+                "{}-{}-{:02}{:02}@twitch.tv",
+                channel,
+                start.weekday().to_string().to_lowercase(),
+                start.hour(),
+                start.minute()
+            )
+        } else {
+            format!("{}@twitch.tv", segment.id)
+        };
+
+        let title = match &segment.category {
+            Some(category) => format!("{}: {}", category, segment.title),
+            None => segment.title.clone(),
+        };
+        events.push(Event {
+            uid,
+            start,
+            end,
+            summary: format!("{} on Twitch", channel),
+            description: title,
+            url: format!("https://www.twitch.tv/{}", channel),
+            repeats_until: None,
+            skipped: Vec::new(),
+        });
+    }
+
+    for (index, later) in occurrences {
+        let event = &mut events[index];
+        let last = *later.last().expect("only inserted with an occurrence");
+        event.repeats_until = Some(last);
+        let mut week = event.start + time::Duration::weeks(1);
+        while week < last {
+            if !later.contains(&week) {
+                event.skipped.push(week);
+            }
+            week += time::Duration::weeks(1);
+        }
+    }
+
+    events
+}
+
+/// Upcoming segments of `channel`, soonest first. Channels without a schedule have none.
+pub async fn segments(
+    state: &AppState,
+    channel: &str,
+) -> Result<Vec<ScheduledSegment>, TwitchRssError> {
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        channel.into(),
+    )
+    .await?;
+
+    get_schedule(
+        &state.cache,
+        state.config.schedule_ttl,
+        &helix_client,
+        &token,
+        user_id,
+    )
+    .await
+}
+
+fn segment_to_item(
+    channel: &str,
+    segment: &ScheduledSegment,
+    time_zone: &TimeZone,
+    locale: Locale,
+) -> FeedItem {
+    let start = segment.start_time.to_utc();
+    let title = match &segment.category {
+        Some(category) => format!("{}: {}", category, segment.title),
+        None => segment.title.clone(),
+    };
+    let description = format!(
+        "{}<br />{}<br />{}: {}<br />{}",
+        locale.starts(&time_zone.format(start)),
+        locale.ends(&time_zone.format(segment.end_time.to_utc())),
+        locale.category(),
+        segment.category.as_deref().unwrap_or(locale.no_category()),
+        segment.title
+    );
+
+    FeedItem {
+        // every occurrence of a recurring segment has its own id
+        id: segment.id.clone(),
+        title,
+        link: format!("https://www.twitch.tv/{}/schedule", channel),
+        description,
+        published: start,
+        sub_only: false,
+        duration: None,
+        thumbnail: None,
+        views: None,
+        category: segment.category.clone(),
+        enclosure: None,
+        tags: Vec::new(),
+        channel: Some(channel.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::format_description::well_known::Rfc3339;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn segment(id: &str, start: &str, recurring: bool) -> ScheduledSegment {
+        let start = OffsetDateTime::parse(start, &Rfc3339).unwrap();
+        let timestamp = |at: OffsetDateTime| Timestamp::new(at.format(&Rfc3339).unwrap()).unwrap();
+        ScheduledSegment {
+            id: id.to_string(),
+            start_time: timestamp(start),
+            end_time: timestamp(start + time::Duration::hours(2)),
+            title: "Speedruns".to_string(),
+            category: Some("Celeste".to_string()),
+            is_recurring: recurring,
+            canceled_until: None,
+        }
+    }
+
+    fn canceled(mut segment: ScheduledSegment) -> ScheduledSegment {
+        segment.canceled_until = Some(segment.end_time.clone());
+        segment
+    }
+
+    #[test]
+    fn folds_weeks_into_a_series() {
+        let events = events(
+            "foo",
+            &[
+                segment("4", "2024-06-22T18:00:00Z", true),
+                segment("1", "2024-06-01T18:00:00Z", true),
+                segment("2", "2024-06-08T18:00:00Z", true),
+                segment("once", "2024-06-05T12:00:00Z", false),
+            ],
+        );
+
+        assert_eq!(events.len(), 2);
+        let weekly = &events[0];
+        assert_eq!(weekly.uid, "foo-saturday-1800@twitch.tv");
+        assert_eq!(weekly.start, datetime!(2024-06-01 18:00 UTC));
+        assert_eq!(weekly.repeats_until, Some(datetime!(2024-06-22 18:00 UTC)));
+        assert_eq!(weekly.skipped, vec![datetime!(2024-06-15 18:00 UTC)]);
+
+        assert_eq!(events[1].uid, "once@twitch.tv");
+        assert_eq!(events[1].repeats_until, None);
+    }
+
+    #[test]
+    fn skips_canceled_broadcasts() {
+        let events = events(
+            "foo",
+            &[
+                canceled(segment("0", "2024-05-25T18:00:00Z", true)),
+                segment("1", "2024-06-01T18:00:00Z", true),
+                canceled(segment("2", "2024-06-08T18:00:00Z", true)),
+                segment("3", "2024-06-15T18:00:00Z", true),
+                canceled(segment("4", "2024-06-22T18:00:00Z", true)),
+                canceled(segment("once", "2024-06-05T12:00:00Z", false)),
+            ],
+        );
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].start, datetime!(2024-06-01 18:00 UTC));
+        assert_eq!(
+            events[0].repeats_until,
+            Some(datetime!(2024-06-15 18:00 UTC))
+        );
+        assert_eq!(events[0].skipped, vec![datetime!(2024-06-08 18:00 UTC)]);
+
+        let body = ical::calendar("foo", &events);
+        assert!(body.contains("RRULE:FREQ=WEEKLY;UNTIL=20240615T180000Z\r\n"));
+        assert!(body.contains("EXDATE:20240608T180000Z\r\n"));
+    }
+
+    #[test]
+    fn describes_segments_in_the_locale() {
+        let mut segment = segment("1", "2024-06-01T18:00:00Z", false);
+        let item = segment_to_item("foo", &segment, &TimeZone::utc(), Locale::De);
+        assert_eq!(
+            item.description,
+            "Beginn: 2024-06-01 18:00 UTC<br />Ende: 2024-06-01 20:00 UTC<br />\
+             Kategorie: Celeste<br />Speedruns"
+        );
+
+        segment.category = None;
+        let item = segment_to_item("foo", &segment, &TimeZone::utc(), Locale::Fr);
+        assert!(item.description.contains("Catégorie: aucune"));
+    }
+
+    #[test]
+    fn ends_series_when_the_slot_moves() {
+        // 20:00 in Berlin, an hour earlier in UTC once summer time ends
+        let events = events(
+            "foo",
+            &[
+                segment("1", "2024-10-19T18:00:00Z", true),
+                segment("2", "2024-10-26T18:00:00Z", true),
+                segment("3", "2024-11-02T19:00:00Z", true),
+                segment("4", "2024-11-09T19:00:00Z", true),
+            ],
+        );
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].repeats_until,
+            Some(datetime!(2024-10-26 18:00 UTC))
+        );
+        assert_eq!(events[1].start, datetime!(2024-11-02 19:00 UTC));
+        assert_eq!(
+            events[1].repeats_until,
+            Some(datetime!(2024-11-09 19:00 UTC))
+        );
+        assert_ne!(events[0].uid, events[1].uid);
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::feed::FeedItem;
+use crate::TwitchRssError;
+
+/// Upper bound on the work a script may do per item, so a runaway loop can't hang a request.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A user supplied Rhai script defining `fn process(item)`.
+///
+/// `item` is a map with `id`, `title`, `link`, `description` and `published` (RFC 3339). The
+/// function returns the (possibly modified) map to keep the item, or `()` to drop it.
+pub struct ItemScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ItemScript {
+    pub fn load(path: &Path) -> Result<Self, TwitchRssError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| TwitchRssError::Script(format!("{}", e)))?;
+
+        Ok(Self { engine, ast })
+    }
+
+    pub fn apply(&self, items: Vec<FeedItem>) -> Vec<FeedItem> {
+        items
+            .into_iter()
+            .filter_map(|item| self.process(item))
+            .collect()
+    }
+
+    fn process(&self, item: FeedItem) -> Option<FeedItem> {
+        let mut map = Map::new();
+        map.insert("id".into(), item.id.clone().into());
+        map.insert("title".into(), item.title.clone().into());
+        map.insert("link".into(), item.link.clone().into());
+        map.insert("description".into(), item.description.clone().into());
+        map.insert(
+            "published".into(),
+            item.published
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default()
+                .into(),
+        );
+
+        let result =
+            self.engine
+                .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "process", (map,));
+
+        // a failing script shouldn't take the feed down with it, keep the item untouched
+        let result = match result {
+            Ok(r) => r,
+            Err(e) => {
+                println!("item script failed for {}: {}", item.id, e);
+                return Some(item);
+            }
+        };
+
+        if result.is_unit() {
+            return None;
+        }
+
+        let Some(map) = result.try_cast::<Map>() else {
+            println!("item script returned neither a map nor () for {}", item.id);
+            return Some(item);
+        };
+
+        let field = |name: &str, default: String| {
+            map.get(name)
+                .and_then(|v| v.clone().into_string().ok())
+                .unwrap_or(default)
+        };
+
+        Some(FeedItem {
+            title: field("title", item.title),
+            link: field("link", item.link),
+            description: field("description", item.description),
+            ..item
+        })
+    }
+}
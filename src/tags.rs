@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use twitch_api2::helix::{HelixClient, Request, RequestGet};
+use twitch_api2::twitch_oauth2;
+use twitch_api2::types::UserId;
+
+use crate::feed::FeedItem;
+use crate::{get_channel_tags, get_token, get_user_id, AppState, TwitchRssError};
+
+/// Get Channel Information, only for the `tags` twitch_api2 doesn't know about.
+#[derive(Serialize)]
+pub struct GetChannelTagsRequest {
+    pub broadcaster_id: UserId,
+}
+
+#[derive(Deserialize, PartialEq)]
+pub struct ChannelTags {
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Request for GetChannelTagsRequest {
+    const PATH: &'static str = "channels";
+    const SCOPE: &'static [twitch_oauth2::Scope] = &[];
+    type Response = Vec<ChannelTags>;
+}
+
+impl RequestGet for GetChannelTagsRequest {}
+
+/// Tags the broadcaster set on `channel`, empty in zero-poll mode which only stores the user.
+pub async fn channel_tags(state: &AppState, channel: &str) -> Result<Vec<String>, TwitchRssError> {
+    #[cfg(feature = "eventsub")]
+    if crate::eventsub::stored_channel(state, channel)
+        .await?
+        .is_some()
+    {
+        return Ok(Vec::new());
+    }
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let user_id = get_user_id(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        channel.into(),
+    )
+    .await?;
+
+    get_channel_tags(
+        &state.cache,
+        state.config.user_id_ttl,
+        &helix_client,
+        &token,
+        user_id,
+    )
+    .await
+}
+
+/// Tags are set on the channel, so every one of its items gets them.
+pub fn annotate(items: Vec<FeedItem>, tags: &[String]) -> Vec<FeedItem> {
+    items
+        .into_iter()
+        .map(|item| FeedItem {
+            tags: tags.to_vec(),
+            ..item
+        })
+        .collect()
+}
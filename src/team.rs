@@ -0,0 +1,70 @@
+use axum::extract::{OriginalUri, Path, Query, State};
+use twitch_api2::helix::HelixClient;
+
+use crate::multi::{self, MAX_BATCH_NAMES};
+use crate::options::{FeedOptions, FeedQuery};
+use crate::render::Rendered;
+use crate::{get_team, get_token, render_feed, AppState, TwitchRssError};
+
+const MAX_TEAM_LEN: usize = 50;
+
+/// `/team/{name}/vod`, the merged VOD feeds of a team's members.
+pub async fn vod(
+    Path(name): Path<String>,
+    Query(query): Query<FeedQuery>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let name = parse_team(&name)?;
+    let options = FeedOptions::try_from(query)?;
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    let team = get_team(
+        &state.cache,
+        state.config.team_ttl,
+        &helix_client,
+        &token,
+        name,
+    )
+    .await?;
+
+    // large teams would cost more Helix requests than a feed is worth, so they are cut short the
+    // same way batch requests are
+    let mut logins = team
+        .users
+        .iter()
+        .map(|u| u.login.to_string())
+        .collect::<Vec<_>>();
+    logins.sort();
+    logins.truncate(MAX_BATCH_NAMES);
+
+    let mut feed = multi::merged_feed(&state, &logins, &options).await?;
+    feed.title = format!("{} Twitch team VODs", team.team.team_display_name);
+    feed.link = format!("https://www.twitch.tv/team/{}", team.team.team_name);
+    feed.image = Some(team.team.thumbnail_url);
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// Team names are lowercase letters, digits and '_'.
+fn parse_team(raw: &str) -> Result<String, TwitchRssError> {
+    let name = raw.trim().to_ascii_lowercase();
+
+    let valid = !name.is_empty()
+        && name.len() <= MAX_TEAM_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(name)
+    } else {
+        Err(TwitchRssError::InvalidTeam(raw.to_string()))
+    }
+}
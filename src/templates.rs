@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tera::{Context, Tera};
+use time::format_description::well_known::Rfc3339;
+
+use twitch_api2::helix::videos::Video;
+
+use crate::feed::{self, Feed, FeedItem, ThumbnailSize};
+use crate::i18n::Locale;
+use crate::render;
+use crate::timezone::TimeZone;
+use crate::TwitchRssError;
+
+/// Template used for item titles, rendered with `video`, `item` and `thumbnail_url`
+pub const ITEM_TITLE: &str = "item_title.txt";
+/// Template used for item descriptions, rendered with `video`, `item` and `thumbnail_url`
+pub const ITEM_DESCRIPTION: &str = "item_description.html";
+/// Template replacing the admin page
+#[cfg(feature = "admin")]
+pub const ADMIN_PAGE: &str = "admin.html";
+/// Template replacing the page of `/channel/{name}`, rendered with `feed`, `items` and `alternates`
+pub const PREVIEW_PAGE: &str = "preview.html";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The built-in item as templates see it, its fields already worked out from the video.
+#[derive(Serialize)]
+struct ItemContext<'a> {
+    url: &'a str,
+    title: &'a str,
+    /// Built-in HTML description
+    description: &'a str,
+    thumbnail: Option<&'a str>,
+    game: Option<&'a str>,
+    tags: &'a [String],
+    /// Length like `3h 12m`, in the feed's locale
+    duration: Option<String>,
+    duration_seconds: Option<u64>,
+    views: Option<u64>,
+    sub_only: bool,
+    /// RFC 3339 timestamp
+    published: String,
+}
+
+impl<'a> ItemContext<'a> {
+    fn new(item: &'a FeedItem, locale: Locale) -> Self {
+        Self {
+            url: &item.link,
+            title: &item.title,
+            description: &item.description,
+            thumbnail: item.thumbnail.as_deref(),
+            game: item.category.as_deref(),
+            tags: &item.tags,
+            duration: item.duration.map(|secs| locale.duration(secs)),
+            duration_seconds: item.duration,
+            views: item.views,
+            sub_only: item.sub_only,
+            published: item.published.format(&Rfc3339).unwrap_or_default(),
+        }
+    }
+}
+
+/// The feed a preview page shows, without its items.
+#[derive(Serialize)]
+struct FeedContext<'a> {
+    title: &'a str,
+    link: &'a str,
+    image: Option<&'a str>,
+    description: Option<&'a str>,
+    language: Option<&'a str>,
+}
+
+/// An item on a preview page, with what the built-in page shows of it.
+#[derive(Serialize)]
+struct PageItem<'a> {
+    #[serde(flatten)]
+    item: ItemContext<'a>,
+    /// The description as plain text, safe to show unlike its HTML
+    text: String,
+    /// Like `2024-06-01 20:00 UTC`, in the configured time zone
+    local_time: String,
+}
+
+/// One of the feeds a preview page links to.
+#[derive(Serialize)]
+struct Alternate<'a> {
+    href: &'a str,
+    content_type: &'a str,
+    label: &'a str,
+}
+
+/// User supplied Tera templates loaded from a directory.
+///
+/// Any template that isn't present falls back to the built-in output, so the directory only
+/// needs to contain the templates that should be customized.
+pub struct Templates {
+    dir: PathBuf,
+    tera: RwLock<Tera>,
+}
+
+impl Templates {
+    pub fn load(dir: PathBuf) -> Result<Self, TwitchRssError> {
+        let tera = load_dir(&dir)?;
+        println!(
+            "loaded templates {:?}",
+            tera.get_template_names().collect::<Vec<_>>()
+        );
+
+        Ok(Self {
+            dir,
+            tera: RwLock::new(tera),
+        })
+    }
+
+    /// Render `name` if the directory contains it.
+    ///
+    /// Rendering failures are logged and treated as a missing template so a broken template
+    /// degrades to the default output rather than failing the whole feed.
+    pub fn render(&self, name: &str, context: &Context) -> Option<String> {
+        let tera = self.tera.read().unwrap_or_else(|e| e.into_inner());
+        if !tera.get_template_names().any(|n| n == name) {
+            return None;
+        }
+
+        match tera.render(name, context) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                println!("failed to render template {}: {:?}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Replace the parts of `item` that have a template with the rendered output.
+    ///
+    /// `description` names the description template, normally [`ITEM_DESCRIPTION`].
+    pub fn apply_to_item(
+        &self,
+        video: &Video,
+        item: FeedItem,
+        size: ThumbnailSize,
+        description: &str,
+        locale: Locale,
+    ) -> FeedItem {
+        let mut context = Context::new();
+        context.insert("video", video);
+        context.insert("item", &ItemContext::new(&item, locale));
+        context.insert("thumbnail_url", &feed::thumbnail_url(video, size));
+
+        FeedItem {
+            title: self.render(ITEM_TITLE, &context).unwrap_or(item.title),
+            description: self
+                .render(description, &context)
+                .unwrap_or(item.description),
+            ..item
+        }
+    }
+
+    /// Render [`PREVIEW_PAGE`] for `feed`, linking to `alternates` as `(href, content type, label)`.
+    pub fn render_page(
+        &self,
+        feed: &Feed,
+        alternates: &[(String, &str, &str)],
+        locale: Locale,
+        time_zone: &TimeZone,
+    ) -> Option<String> {
+        let items = feed
+            .items
+            .iter()
+            .map(|item| PageItem {
+                item: ItemContext::new(item, locale),
+                text: render::plain_text(&item.description),
+                local_time: time_zone.format(item.published),
+            })
+            .collect::<Vec<_>>();
+        let alternates = alternates
+            .iter()
+            .map(|(href, content_type, label)| Alternate {
+                href,
+                content_type,
+                label,
+            })
+            .collect::<Vec<_>>();
+
+        let mut context = Context::new();
+        context.insert(
+            "feed",
+            &FeedContext {
+                title: &feed.title,
+                link: &feed.link,
+                image: feed.image.as_deref(),
+                description: feed.description.as_deref(),
+                language: feed.language.as_deref(),
+            },
+        );
+        context.insert("items", &items);
+        context.insert("alternates", &alternates);
+        self.render(PREVIEW_PAGE, &context)
+    }
+
+    /// Poll the template directory and reload everything whenever a file changes.
+    pub fn watch(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut last = snapshot(&self.dir);
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+
+                let current = snapshot(&self.dir);
+                if current == last {
+                    continue;
+                }
+                last = current;
+
+                // keep serving the previous templates if the new ones don't parse
+                match load_dir(&self.dir) {
+                    Ok(tera) => {
+                        println!("reloaded templates from {}", self.dir.display());
+                        *self.tera.write().unwrap_or_else(|e| e.into_inner()) = tera;
+                    }
+                    Err(e) => println!("{}", e),
+                }
+            }
+        });
+    }
+}
+
+fn load_dir(dir: &Path) -> Result<Tera, TwitchRssError> {
+    let glob = dir.join("**").join("*");
+    Tera::new(&glob.to_string_lossy()).map_err(|e| TwitchRssError::Template(format!("{:?}", e)))
+}
+
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, Option<SystemTime>, u64)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+
+        if meta.is_dir() {
+            collect_files(&entry.path(), files);
+        } else {
+            files.push((entry.path(), meta.modified().ok(), meta.len()));
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use std::path::Path;
+
+use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+/// Where the system keeps the IANA time zone database, `tzdata` on Debian.
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// Longest time zone name accepted, the longest in the database is about half of it.
+const MAX_NAME_LEN: usize = 64;
+
+/// A time zone from the IANA database, like `Europe/Berlin`, for dates shown to people.
+///
+/// Feed timestamps stay in UTC, only dates written into descriptions are local.
+#[derive(Clone, Debug)]
+pub struct TimeZone {
+    name: String,
+    /// Transition times in Unix seconds, ascending, and the local time type from then on
+    transitions: Vec<(i64, usize)>,
+    types: Vec<LocalType>,
+    /// Rule for times after the last transition, from the file's footer
+    rule: Option<Rule>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct LocalType {
+    /// Seconds east of UTC
+    offset: i32,
+    abbreviation: String,
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self::utc()
+    }
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            name: "UTC".to_string(),
+            transitions: Vec::new(),
+            types: vec![LocalType {
+                offset: 0,
+                abbreviation: "UTC".to_string(),
+            }],
+            rule: None,
+        }
+    }
+
+    /// Read `name` from the system's time zone database.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let invalid = || format!("{:?} is not a time zone like Europe/Berlin", name);
+        if name == "UTC" {
+            return Ok(Self::utc());
+        }
+
+        let valid = name.len() <= MAX_NAME_LEN
+            && name.split('/').all(|part| {
+                !part.is_empty()
+                    && !part.starts_with('.')
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+            });
+        if !valid {
+            return Err(invalid());
+        }
+
+        let data = std::fs::read(Path::new(ZONEINFO_DIR).join(name)).map_err(|_| invalid())?;
+        parse_tzif(name, &data).ok_or_else(invalid)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Offset from UTC and abbreviation of local time at `at`.
+    fn local_type(&self, at: OffsetDateTime) -> (UtcOffset, &str) {
+        let secs = at.unix_timestamp();
+        let after = self.transitions.partition_point(|(t, _)| *t <= secs);
+
+        let local = match (after, &self.rule) {
+            (_, Some(rule)) if after == self.transitions.len() => rule.local_type(at),
+            // times before the first transition use the first type
+            (0, _) => &self.types[0],
+            _ => &self.types[self.transitions[after - 1].1],
+        };
+        let offset = UtcOffset::from_whole_seconds(local.offset).unwrap_or(UtcOffset::UTC);
+
+        (offset, &local.abbreviation)
+    }
+
+    /// `at` in local time.
+    pub fn local(&self, at: OffsetDateTime) -> OffsetDateTime {
+        at.to_offset(self.local_type(at).0)
+    }
+
+    /// `at` for people, like `2024-06-01 20:00 CEST`.
+    pub fn format(&self, at: OffsetDateTime) -> String {
+        let (offset, abbreviation) = self.local_type(at);
+        let local = at.to_offset(offset);
+        format!(
+            "{} {:02}:{:02} {}",
+            local.date(),
+            local.hour(),
+            local.minute(),
+            abbreviation
+        )
+    }
+}
+
+/// Parse a TZif file (RFC 8536), using the 64-bit data of version 2 and later.
+fn parse_tzif(name: &str, data: &[u8]) -> Option<TimeZone> {
+    let header = Header::parse(data)?;
+    let (header, body, time_size) = match header.version {
+        0 => (header, &data[44..], 4),
+        _ => {
+            let v2 = data.get(44 + header.block_len(4)..)?;
+            (Header::parse(v2)?, &v2[44..], 8)
+        }
+    };
+
+    let mut at = 0;
+    let mut take = |len: usize| {
+        let bytes = body.get(at..at + len);
+        at += len;
+        bytes
+    };
+
+    let times = take(header.time_count * time_size)?;
+    let indices = take(header.time_count)?;
+    let infos = take(header.type_count * 6)?;
+    let chars = take(header.char_count)?;
+    take(header.block_len(time_size) - header.data_len(time_size))?;
+
+    let abbreviation = |index: usize| {
+        let rest = chars.get(index..)?;
+        let end = rest.iter().position(|&b| b == 0)?;
+        String::from_utf8(rest[..end].to_vec()).ok()
+    };
+    let types = infos
+        .chunks(6)
+        .map(|info| {
+            Some(LocalType {
+                offset: i32::from_be_bytes(info[..4].try_into().ok()?),
+                abbreviation: abbreviation(info[5] as usize)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let transitions = times
+        .chunks(time_size)
+        .zip(indices)
+        .map(|(time, &index)| {
+            let time = match time_size {
+                4 => i32::from_be_bytes(time.try_into().ok()?) as i64,
+                _ => i64::from_be_bytes(time.try_into().ok()?),
+            };
+            types.get(index as usize)?;
+            Some((time, index as usize))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if types.is_empty() {
+        return None;
+    }
+
+    // the footer is a POSIX TZ string between newlines, empty if there is no rule
+    let rule = match time_size {
+        8 => {
+            let footer = body.get(at..)?;
+            let footer = std::str::from_utf8(footer).ok()?;
+            let footer = footer.strip_prefix('\n')?.split('\n').next()?;
+            Rule::parse(footer)
+        }
+        _ => None,
+    };
+
+    Some(TimeZone {
+        name: name.to_string(),
+        transitions,
+        types,
+        rule,
+    })
+}
+
+struct Header {
+    /// 0 for the first version, the ASCII digit otherwise
+    version: u8,
+    ut_count: usize,
+    std_count: usize,
+    leap_count: usize,
+    time_count: usize,
+    type_count: usize,
+    char_count: usize,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let count = |i: usize| {
+            let bytes = data.get(20 + i * 4..24 + i * 4)?;
+            Some(u32::from_be_bytes(bytes.try_into().ok()?) as usize)
+        };
+
+        Some(Self {
+            version: *data.get(4)?,
+            ut_count: count(0)?,
+            std_count: count(1)?,
+            leap_count: count(2)?,
+            time_count: count(3)?,
+            type_count: count(4)?,
+            char_count: count(5)?,
+        })
+    }
+
+    /// Length of the transitions, types and abbreviations.
+    fn data_len(&self, time_size: usize) -> usize {
+        self.time_count * (time_size + 1) + self.type_count * 6 + self.char_count
+    }
+
+    /// Length of the whole data block following the header.
+    fn block_len(&self, time_size: usize) -> usize {
+        self.data_len(time_size)
+            + self.leap_count * (time_size + 4)
+            + self.std_count
+            + self.ut_count
+    }
+}
+
+/// A POSIX TZ string like `CET-1CEST,M3.5.0,M10.5.0/3`, how zones that still change their clocks
+/// do so after the last transition in the file.
+#[derive(Clone, Debug)]
+struct Rule {
+    std: LocalType,
+    dst: Option<(LocalType, RuleDate, RuleDate)>,
+}
+
+/// Day and local time, in seconds, daylight saving time starts or ends.
+#[derive(Clone, Debug)]
+struct RuleDate {
+    day: RuleDay,
+    time: i64,
+}
+
+#[derive(Clone, Debug)]
+enum RuleDay {
+    /// Month, week from 1 to 5 (the last) and weekday from 0 (Sunday)
+    Weekday(u8, u8, u8),
+    /// Day of the year from 1, February 29 never counted
+    Julian(u16),
+    /// Day of the year from 0, February 29 counted
+    Ordinal(u16),
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Self> {
+        let mut rest = s;
+        let std_name = take_name(&mut rest)?;
+        let std_offset = -take_time(&mut rest)?;
+        let std = LocalType {
+            offset: i32::try_from(std_offset).ok()?,
+            abbreviation: std_name,
+        };
+        if rest.is_empty() {
+            return Some(Self { std, dst: None });
+        }
+
+        let dst_name = take_name(&mut rest)?;
+        let dst_offset = match rest.starts_with(',') {
+            true => std_offset + 3600,
+            false => -take_time(&mut rest)?,
+        };
+        let dst = LocalType {
+            offset: i32::try_from(dst_offset).ok()?,
+            abbreviation: dst_name,
+        };
+
+        let mut dates = rest.strip_prefix(',')?.split(',');
+        let start = RuleDate::parse(dates.next()?)?;
+        let end = RuleDate::parse(dates.next()?)?;
+        if dates.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            std,
+            dst: Some((dst, start, end)),
+        })
+    }
+
+    fn local_type(&self, at: OffsetDateTime) -> &LocalType {
+        let Some((dst, start, end)) = &self.dst else {
+            return &self.std;
+        };
+
+        let year = at.year();
+        let secs = at.unix_timestamp();
+        // the start is given in standard time, the end in daylight saving time
+        let (Some(start), Some(end)) = (
+            start.unix_timestamp(year, self.std.offset),
+            end.unix_timestamp(year, dst.offset),
+        ) else {
+            return &self.std;
+        };
+
+        let in_dst = match start <= end {
+            true => start <= secs && secs < end,
+            // the southern hemisphere is on daylight saving time over the new year
+            false => !(end <= secs && secs < start),
+        };
+        match in_dst {
+            true => dst,
+            false => &self.std,
+        }
+    }
+}
+
+impl RuleDate {
+    fn parse(s: &str) -> Option<Self> {
+        let (day, time) = match s.split_once('/') {
+            Some((day, time)) => {
+                let mut time = time;
+                let secs = take_time(&mut time)?;
+                if !time.is_empty() {
+                    return None;
+                }
+                (day, secs)
+            }
+            None => (s, 2 * 3600),
+        };
+
+        let day = if let Some(mwd) = day.strip_prefix('M') {
+            let mut parts = mwd.split('.').map(|p| p.parse::<u8>().ok());
+            let (month, week, weekday) = (parts.next()??, parts.next()??, parts.next()??);
+            let valid = (1..=12).contains(&month) && (1..=5).contains(&week) && weekday <= 6;
+            if !valid || parts.next().is_some() {
+                return None;
+            }
+            RuleDay::Weekday(month, week, weekday)
+        } else if let Some(julian) = day.strip_prefix('J') {
+            RuleDay::Julian(julian.parse().ok().filter(|d| (1..=365).contains(d))?)
+        } else {
+            RuleDay::Ordinal(day.parse().ok().filter(|d| *d <= 365)?)
+        };
+
+        Some(Self { day, time })
+    }
+
+    /// When this date is in `year`, on a clock `offset` seconds east of UTC.
+    fn unix_timestamp(&self, year: i32, offset: i32) -> Option<i64> {
+        let date = match self.day {
+            RuleDay::Weekday(month, week, weekday) => {
+                let month = Month::try_from(month).ok()?;
+                let first = Date::from_calendar_date(year, month, 1).ok()?;
+                let first_weekday = first.weekday().number_days_from_sunday();
+                let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+                // week 5 is the last one, which may be the fourth
+                while day > month.length(year) {
+                    day -= 7;
+                }
+                first.replace_day(day).ok()?
+            }
+            RuleDay::Julian(day) => {
+                let leap = time::util::is_leap_year(year) && day >= 60;
+                Date::from_ordinal_date(year, day + leap as u16).ok()?
+            }
+            RuleDay::Ordinal(day) => Date::from_ordinal_date(year, day + 1).ok()?,
+        };
+
+        let midnight = date.midnight().assume_utc().unix_timestamp();
+        Some(midnight + self.time - offset as i64)
+    }
+}
+
+/// Take a zone abbreviation, either letters or anything in angle brackets like `<+03>`.
+fn take_name(rest: &mut &str) -> Option<String> {
+    let (name, after) = match rest.strip_prefix('<') {
+        Some(quoted) => {
+            let end = quoted.find('>')?;
+            (&quoted[..end], &quoted[end + 1..])
+        }
+        None => {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        }
+    };
+    if name.len() < 3 {
+        return None;
+    }
+    *rest = after;
+    Some(name.to_string())
+}
+
+/// Take `[+-]hh[:mm[:ss]]` as seconds.
+fn take_time(rest: &mut &str) -> Option<i64> {
+    let (sign, unsigned) = match rest.as_bytes().first() {
+        Some(b'-') => (-1, &rest[1..]),
+        Some(b'+') => (1, &rest[1..]),
+        _ => (1, *rest),
+    };
+    let end = unsigned
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(unsigned.len());
+
+    let mut secs = 0;
+    for (i, part) in unsigned[..end].split(':').enumerate() {
+        let value = part.parse::<i64>().ok()?;
+        secs += match i {
+            0 if value <= 167 => value * 3600,
+            1 | 2 if value < 60 => value * [60, 1][i - 1],
+            _ => return None,
+        };
+    }
+    *rest = &unsigned[end..];
+    Some(sign * secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn at(secs: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(secs).expect("valid timestamp")
+    }
+
+    fn zone(rule: &str) -> TimeZone {
+        TimeZone {
+            name: "Test".to_string(),
+            transitions: Vec::new(),
+            types: vec![LocalType {
+                offset: 0,
+                abbreviation: "LMT".to_string(),
+            }],
+            rule: Rule::parse(rule),
+        }
+    }
+
+    #[test]
+    fn follows_daylight_saving_rules() {
+        let berlin = zone("CET-1CEST,M3.5.0,M10.5.0/3");
+        assert_eq!(
+            berlin.format(datetime!(2024-01-15 12:00 UTC)),
+            "2024-01-15 13:00 CET"
+        );
+        assert_eq!(
+            berlin.format(datetime!(2024-07-01 18:30 UTC)),
+            "2024-07-01 20:30 CEST"
+        );
+        // clocks go forward at 01:00 UTC on the last Sunday of March
+        assert_eq!(
+            berlin.format(datetime!(2024-03-31 0:59 UTC)),
+            "2024-03-31 01:59 CET"
+        );
+        assert_eq!(
+            berlin.format(datetime!(2024-03-31 1:00 UTC)),
+            "2024-03-31 03:00 CEST"
+        );
+    }
+
+    #[test]
+    fn handles_southern_and_fixed_zones() {
+        let sydney = zone("AEST-10AEDT,M10.1.0,M4.1.0/3");
+        assert_eq!(
+            sydney.format(datetime!(2024-01-01 0:00 UTC)),
+            "2024-01-01 11:00 AEDT"
+        );
+        assert_eq!(
+            sydney.format(datetime!(2024-07-01 0:00 UTC)),
+            "2024-07-01 10:00 AEST"
+        );
+
+        let kolkata = zone("IST-5:30");
+        assert_eq!(kolkata.format(at(0)), "1970-01-01 05:30 IST");
+        assert_eq!(zone("<-03>3").format(at(0)), "1969-12-31 21:00 -03");
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert!(TimeZone::load("../etc/passwd").is_err());
+        assert!(TimeZone::load("Europe/.hidden").is_err());
+        assert!(TimeZone::load("").is_err());
+        assert_eq!(TimeZone::load("UTC").map(|z| z.name).as_deref(), Ok("UTC"));
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+use twitch_api2::helix::videos::Video;
+
+use crate::TwitchRssError;
+
+/// Per-channel settings for a channel the server has been told to track.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackedChannel {
+    /// Overrides the default video cache TTL (seconds) for this channel
+    pub ttl: Option<u64>,
+    /// URLs a JSON description of every new video is posted to
+    pub notify: Vec<String>,
+    /// Keep every video seen in the feeds, also once Twitch deleted it
+    pub archive: bool,
+}
+
+impl TrackedChannel {
+    /// Whether the videos have to be checked without readers asking for them.
+    pub fn is_watched(&self) -> bool {
+        self.archive || !self.notify.is_empty()
+    }
+}
+
+/// Most videos archived per channel, the oldest are dropped beyond this.
+const MAX_ARCHIVED: usize = 500;
+
+/// Runtime information about a tracked channel, not persisted.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ChannelStatus {
+    /// When a feed was last generated for the channel (RFC 3339)
+    pub last_refresh: Option<String>,
+    /// Number of items in the last generated feed
+    pub item_count: usize,
+}
+
+/// The set of tracked channels, keyed by login.
+///
+/// Changes are written back to `path` (as JSON) when one is configured, otherwise they only live
+/// in memory.
+pub struct TrackedChannels {
+    path: Option<PathBuf>,
+    channels: RwLock<BTreeMap<String, TrackedChannel>>,
+    status: RwLock<HashMap<String, ChannelStatus>>,
+    /// Videos of channels with `archive` on, newest first, written next to `path`
+    archived: RwLock<BTreeMap<String, Vec<Video>>>,
+    /// Creation time of the newest video of each channel seen by [`Self::new_videos`]
+    newest: RwLock<HashMap<String, OffsetDateTime>>,
+}
+
+impl TrackedChannels {
+    pub fn load(path: Option<PathBuf>) -> Result<Self, TwitchRssError> {
+        let channels = match &path {
+            Some(p) if p.exists() => {
+                let data = std::fs::read(p).map_err(storage_error)?;
+                serde_json::from_slice(&data).map_err(storage_error)?
+            }
+            _ => BTreeMap::new(),
+        };
+
+        let archived = match path.as_deref().map(archive_path) {
+            Some(p) if p.exists() => {
+                let data = std::fs::read(&p).map_err(storage_error)?;
+                serde_json::from_slice(&data).map_err(storage_error)?
+            }
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            path,
+            channels: RwLock::new(channels),
+            status: RwLock::new(HashMap::new()),
+            archived: RwLock::new(archived),
+            newest: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub async fn list(&self) -> BTreeMap<String, TrackedChannel> {
+        self.channels.read().await.clone()
+    }
+
+    pub async fn get(&self, login: &str) -> Option<TrackedChannel> {
+        self.channels.read().await.get(login).cloned()
+    }
+
+    /// Add a channel, or replace the settings of an already tracked one.
+    pub async fn set(&self, login: String, channel: TrackedChannel) -> Result<(), TwitchRssError> {
+        let mut channels = self.channels.write().await;
+        channels.insert(login, channel);
+        self.persist(&channels).await
+    }
+
+    /// Stop tracking a channel, returning whether it was tracked at all.
+    pub async fn remove(&self, login: &str) -> Result<bool, TwitchRssError> {
+        let mut channels = self.channels.write().await;
+        let removed = channels.remove(login).is_some();
+        if removed {
+            self.status.write().await.remove(login);
+            self.newest.write().await.remove(login);
+            let mut archived = self.archived.write().await;
+            if archived.remove(login).is_some() {
+                self.persist_archive(&archived).await?;
+            }
+            self.persist(&channels).await?;
+        }
+        Ok(removed)
+    }
+
+    pub async fn status(&self) -> HashMap<String, ChannelStatus> {
+        self.status.read().await.clone()
+    }
+
+    /// Note that a feed with `item_count` items was just generated for `login`, if it is tracked.
+    pub async fn record_refresh(&self, login: &str, item_count: usize) {
+        if !self.channels.read().await.contains_key(login) {
+            return;
+        }
+
+        let last_refresh = time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .ok();
+
+        self.status.write().await.insert(
+            login.to_string(),
+            ChannelStatus {
+                last_refresh,
+                item_count,
+            },
+        );
+    }
+
+    /// Add `videos` of `login` to its archive if archiving is on for it, and return the archived
+    /// videos that aren't among them, newest first.
+    pub async fn archive(&self, login: &str, videos: &[Video]) -> Vec<Video> {
+        if !self.get(login).await.is_some_and(|c| c.archive) {
+            return Vec::new();
+        }
+
+        let mut archived = self.archived.write().await;
+        let channel = archived.entry(login.to_string()).or_default();
+        let added = videos.iter().any(|v| !channel.iter().any(|a| a.id == v.id));
+        // the latest copy wins, titles and view counts change
+        channel.retain(|a| !videos.iter().any(|v| v.id == a.id));
+        let older = channel.clone();
+        channel.extend(videos.iter().cloned());
+        channel.sort_by_key(|v| std::cmp::Reverse(v.created_at.to_utc()));
+        channel.truncate(MAX_ARCHIVED);
+
+        // only new videos are written, changed titles and view counts aren't worth a write
+        if added {
+            if let Err(e) = self.persist_archive(&archived).await {
+                println!("archiving videos of {}: {}", login, e);
+            }
+        }
+        older
+    }
+
+    /// The videos in `videos` (all recent videos of `login`) created after the newest one seen in
+    /// the previous call. The first call only remembers where to start.
+    pub async fn new_videos<'a>(&self, login: &str, videos: &'a [Video]) -> Vec<&'a Video> {
+        let Some(newest) = videos.iter().map(|v| v.created_at.to_utc()).max() else {
+            return Vec::new();
+        };
+
+        let mut seen = self.newest.write().await;
+        let new = match seen.get(login) {
+            Some(&previous) => videos
+                .iter()
+                .filter(|v| v.created_at.to_utc() > previous)
+                .collect(),
+            None => Vec::new(),
+        };
+        let previous = seen.entry(login.to_string()).or_insert(newest);
+        *previous = (*previous).max(newest);
+        new
+    }
+
+    async fn persist(
+        &self,
+        channels: &BTreeMap<String, TrackedChannel>,
+    ) -> Result<(), TwitchRssError> {
+        match &self.path {
+            Some(path) => write(path, channels).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn persist_archive(
+        &self,
+        archived: &BTreeMap<String, Vec<Video>>,
+    ) -> Result<(), TwitchRssError> {
+        match &self.path {
+            Some(path) => write(&archive_path(path), archived).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Archived videos are kept next to the tracked channels, `tracked.json` has `tracked.archive.json`.
+fn archive_path(path: &Path) -> PathBuf {
+    path.with_extension("archive.json")
+}
+
+async fn write(path: &Path, value: &impl Serialize) -> Result<(), TwitchRssError> {
+    let data = serde_json::to_vec_pretty(value).map_err(storage_error)?;
+
+    // write to a temporary file first so a crash mid-write can't truncate the real one
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, data).await.map_err(storage_error)?;
+    tokio::fs::rename(&tmp, path).await.map_err(storage_error)
+}
+
+fn storage_error(e: impl std::fmt::Display) -> TwitchRssError {
+    TwitchRssError::Storage(format!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str, created_at: &str) -> Video {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "stream_id": null,
+            "user_id": "123",
+            "user_login": "foo",
+            "user_name": "Foo",
+            "title": "Some stream",
+            "description": "",
+            "created_at": created_at,
+            "published_at": created_at,
+            "url": format!("https://www.twitch.tv/videos/{}", id),
+            "thumbnail_url": "",
+            "viewable": "public",
+            "view_count": 1,
+            "language": "en",
+            "type": "archive",
+            "duration": "1h",
+            "muted_segments": null,
+        }))
+        .unwrap()
+    }
+
+    fn ids(videos: &[Video]) -> Vec<&str> {
+        videos.iter().map(|v| v.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn archives_only_when_asked() {
+        let tracked = TrackedChannels::load(None).unwrap();
+        let first = [
+            video("2", "2024-06-02T00:00:00Z"),
+            video("1", "2024-06-01T00:00:00Z"),
+        ];
+        let later = [
+            video("3", "2024-06-03T00:00:00Z"),
+            video("2", "2024-06-02T00:00:00Z"),
+        ];
+
+        tracked
+            .set("foo".to_string(), TrackedChannel::default())
+            .await
+            .unwrap();
+        assert!(tracked.archive("foo", &first).await.is_empty());
+
+        let archiving = TrackedChannel {
+            archive: true,
+            ..TrackedChannel::default()
+        };
+        tracked.set("foo".to_string(), archiving).await.unwrap();
+        assert!(tracked.archive("foo", &first).await.is_empty());
+        // video 1 was deleted from Twitch in the meantime
+        assert_eq!(ids(&tracked.archive("foo", &later).await), ["1"]);
+        assert!(tracked.archive("bar", &later).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn finds_new_videos() {
+        let tracked = TrackedChannels::load(None).unwrap();
+        let first = [video("1", "2024-06-01T00:00:00Z")];
+        let later = [
+            video("3", "2024-06-03T00:00:00Z"),
+            video("2", "2024-06-02T00:00:00Z"),
+            video("1", "2024-06-01T00:00:00Z"),
+        ];
+
+        assert!(tracked.new_videos("foo", &first).await.is_empty());
+        let new = tracked.new_videos("foo", &later).await;
+        assert_eq!(
+            new.iter().map(|v| v.id.as_str()).collect::<Vec<_>>(),
+            ["3", "2"]
+        );
+        assert!(tracked.new_videos("foo", &later).await.is_empty());
+        assert!(tracked.new_videos("foo", &[]).await.is_empty());
+    }
+}
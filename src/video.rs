@@ -0,0 +1,78 @@
+use axum::extract::{OriginalUri, Path, State};
+use axum::Json;
+use twitch_api2::helix::videos::Video;
+use twitch_api2::helix::HelixClient;
+
+use crate::feed::Feed;
+use crate::latest::VideoSummary;
+use crate::options::ItemOptions;
+use crate::render::Rendered;
+use crate::{get_token, get_video, provider, render_feed, AppState, TwitchRssError};
+
+/// `/video/{id}`, a feed with just that video.
+pub async fn feed(
+    Path(id): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    State(state): State<AppState>,
+) -> Result<Rendered, TwitchRssError> {
+    let video = fetch(&state, &id).await?;
+
+    let feed = Feed {
+        title: format!("{}: {}", video.user_name, video.title),
+        link: video.url.clone(),
+        image: None,
+        description: None,
+        language: None,
+        self_link: None,
+        hub: None,
+        ttl: Some(state.config.videos_ttl),
+        items: provider::twitch_items(
+            &state,
+            video.user_login.as_str(),
+            std::slice::from_ref(&video),
+            &ItemOptions::default(),
+        ),
+    };
+
+    Ok(render_feed(&state, &uri, feed))
+}
+
+/// `/video/{id}/json`, the same video as JSON.
+pub async fn json(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<VideoSummary>, TwitchRssError> {
+    let video = fetch(&state, &id).await?;
+
+    let size = state
+        .overrides
+        .get(video.user_login.as_str())
+        .thumbnail_size
+        .unwrap_or_default();
+    Ok(Json(VideoSummary::new(&video, size)))
+}
+
+async fn fetch(state: &AppState, id: &str) -> Result<Video, TwitchRssError> {
+    // video ids are numeric, anything else can't exist
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(TwitchRssError::InvalidVideo(id.to_string()));
+    }
+
+    let token = get_token(
+        &state.client,
+        state.client_id.clone(),
+        state.client_secret.clone(),
+    )
+    .await?;
+
+    let helix_client = HelixClient::with_client(state.client.clone());
+
+    get_video(
+        &state.cache,
+        state.config.videos_ttl,
+        &helix_client,
+        &token,
+        id.into(),
+    )
+    .await
+}
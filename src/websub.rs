@@ -0,0 +1,55 @@
+use axum::http::Uri;
+
+use crate::{self_link, AppState};
+
+/// Paths of the feeds of a channel the hub is told about. Readers subscribed with a query
+/// string aren't pinged, the hub only knows the URLs it was given.
+const FEED_PATHS: [&str; 3] = ["vod", "vod.atom", "vod.json"];
+
+/// The hub a feed of `login` served at `uri` advertises, for the feeds [`publish`] pings: those of
+/// tracked channels, which EventSub reports changes of, at one of [`FEED_PATHS`] without a query.
+pub async fn hub(state: &AppState, login: &str, uri: &Uri) -> Option<String> {
+    let hub = state.config.channel.hub.as_ref()?;
+    state.eventsub.as_ref()?;
+
+    let requested = self_link(state, uri)?;
+    let published = FEED_PATHS
+        .iter()
+        .filter_map(|path| {
+            state
+                .config
+                .public_url(&format!("/channel/{}/{}", login, path))
+        })
+        .any(|url| url == requested);
+    (published && state.tracked.get(login).await.is_some()).then(|| hub.clone())
+}
+
+/// Tell the WebSub hub, if there is one, that the VOD feeds of `login` changed, so it pushes them
+/// to subscribers instead of them polling.
+///
+/// The hub fetches the feeds right away, the cached videos have to be dropped before.
+pub async fn publish(state: &AppState, login: &str) {
+    let Some(hub) = &state.config.channel.hub else {
+        return;
+    };
+
+    for path in FEED_PATHS {
+        let Some(url) = state
+            .config
+            .public_url(&format!("/channel/{}/{}", login, path))
+        else {
+            return;
+        };
+
+        let response = state
+            .client
+            .post(hub)
+            .form(&[("hub.mode", "publish"), ("hub.url", url.as_str())])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = response {
+            println!("pinging {} about {} failed: {}", hub, url, e);
+        }
+    }
+}